@@ -1,4 +1,7 @@
-use std::time::Instant;
+use tokio::time::Instant;
+use crate::system_input::system_input::MouseButton;
+use crate::system_input::gamepad_output::{GamepadButton, GamepadStick, GamepadTrigger};
+use crate::actions::movement::{Easing, MouseTarget};
 
 #[derive(Debug)]
 #[derive(Clone)]
@@ -6,16 +9,93 @@ use std::time::Instant;
 pub enum Action {
     KeyRawDown(u16),
     KeyRawUp(u16),
+    Press { keys: Vec<u16>, hold_ms: u64 },
     MoveMouseOf(i32, i32),
+    MoveMouseTo(i32, i32),
+    MoveMouseToNormalized(f32, f32),
+    SmoothMoveMouse { target: MouseTarget, duration_ms: u64, easing: Easing, tick_rate: u32 },
+    MouseDown(MouseButton),
+    MouseUp(MouseButton),
+    MouseClick(MouseButton),
+    ScrollOf(i32, i32),
+    GamepadButtonDown(GamepadButton),
+    GamepadButtonUp(GamepadButton),
+    GamepadPress { buttons: Vec<GamepadButton>, hold_ms: u64 },
+    /// Stick stays in position, or goes back to the center after the duration
+    MoveStick { stick: GamepadStick, x: f32, y: f32, duration_ms: Option<u64> },
+    /// Trigger stays pulled, or is released after the duration
+    PullTrigger { trigger: GamepadTrigger, value: f32, duration_ms: Option<u64> },
+    TypeText(String, u64),
     WaitFor(u64),
     WaitUntil(Instant),
     Sequence(Vec<Action>),
     AtomicSequence(Vec<Action>)
 }
 
+#[derive(Debug)]
 #[derive(Clone)]
 #[derive(PartialEq)]
 pub enum ActionCategory {
     WithCategory(String, Action),
     Uncategorized(Action)
 }
+
+/// Viewer whose event triggered the actions
+#[derive(Debug)]
+#[derive(Clone)]
+#[derive(PartialEq)]
+#[derive(Default)]
+pub struct ActionOrigin {
+    pub user: String,
+    pub is_subscriber: bool,
+    /// Bits cheered with the event, 0 when it isn't a cheer
+    pub bits: u64
+}
+
+/// What happens to the running action interrupted by a preempting one
+#[derive(Debug)]
+#[derive(Clone, Copy)]
+#[derive(PartialEq)]
+pub enum Preemption {
+    /// Back first in line, it runs again from its first step
+    Resume,
+    Drop
+}
+
+/// Place of the actions of a mapping in their queue, higher priority actions start first
+#[derive(Debug)]
+#[derive(Clone, Copy)]
+#[derive(PartialEq)]
+#[derive(Default)]
+pub struct Scheduling {
+    pub priority: i32,
+    /// Interrupts a running action of lower priority
+    pub preempt: Option<Preemption>
+}
+
+/// Actions of a mapping with the viewer that triggered them
+#[derive(Debug)]
+#[derive(Clone)]
+#[derive(PartialEq)]
+pub struct TriggeredActions {
+    pub category: ActionCategory,
+    pub origin: ActionOrigin,
+    pub scheduling: Scheduling
+}
+
+/// Action waiting in a category queue, with who triggered it and the time it got there
+#[derive(Debug)]
+#[derive(Clone)]
+#[derive(PartialEq)]
+pub struct QueuedAction {
+    pub action: Action,
+    pub origin: ActionOrigin,
+    pub scheduling: Scheduling,
+    pub queued_at: Instant
+}
+
+impl QueuedAction {
+    pub fn new(action: Action, origin: ActionOrigin, scheduling: Scheduling) -> Self {
+        QueuedAction { action, origin, scheduling, queued_at: Instant::now() }
+    }
+}
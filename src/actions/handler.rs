@@ -1,63 +1,453 @@
 use crate::actions::action::Action;
-use crate::system_input::system_input::SystemInput;
-use crate::system_input::custom_system_input::custom_system_input::CustomSystemInput;
-use std::time::{Instant, Duration};
+use crate::actions::movement::{interpolate, tick_waits, Easing, MouseTarget};
+use crate::actions::pause::PauseConditions;
+use crate::actions::window_guard::{WindowGuard, WindowPolicy};
+use crate::system_input::system_input::{SystemInput, MouseButton};
+use crate::system_input::gamepad_output::{GamepadOutput, GamepadButton, GamepadStick, GamepadTrigger, SharedGamepad};
+use std::collections::VecDeque;
 use std::ops::Add;
+use tokio::time::{Instant, Duration};
+use crate::{s};
 
-pub struct ActionHandler {
-    input_system: CustomSystemInput
+#[derive(Debug)]
+#[derive(Clone, Copy)]
+#[derive(PartialEq)]
+pub enum HeldInput {
+    Key(u16),
+    MouseButton(MouseButton),
+    GamepadButton(GamepadButton),
+    /// Stick out of the center
+    GamepadStick(GamepadStick),
+    /// Trigger not fully released
+    GamepadTrigger(GamepadTrigger)
 }
 
-impl Default for ActionHandler {
-    fn default() -> Self {
-        ActionHandler { input_system: CustomSystemInput::new() }
-    }
+pub struct ActionHandler {
+    input_system: Box<dyn SystemInput>,
+    pending_releases: Vec<(Instant, Vec<HeldInput>)>,
+    held_inputs: Vec<HeldInput>,
+    pause_conditions: PauseConditions,
+    window_guard: Option<WindowGuard>,
+    gamepad: Option<SharedGamepad>,
+    /// Released while the queue was paused, the running action doesn't release them again
+    released_by_pause: Vec<HeldInput>,
+    /// Reads the state of the PC when the input backend can't
+    state_input: Option<Box<dyn SystemInput>>
 }
 
 impl ActionHandler {
-    pub fn run(&mut self, actions: &mut Vec<Action>) {
+    pub fn new(input_system: Box<dyn SystemInput>, pause_conditions: PauseConditions, window_guard: Option<WindowGuard>, gamepad: Option<SharedGamepad>) -> Self {
+        ActionHandler { input_system, pending_releases: Vec::new(), held_inputs: Vec::new(), pause_conditions, window_guard, gamepad, released_by_pause: Vec::new(), state_input: None }
+    }
+
+    /// Pause conditions and target window read on this input rather than on the one sending the actions
+    pub fn read_state_on(mut self, state_input: Box<dyn SystemInput>) -> Self {
+        self.state_input = Some(state_input);
+        self
+    }
+
+    fn state_input(&self) -> &dyn SystemInput {
+        self.state_input.as_deref().unwrap_or_else(|| self.input_system.as_ref())
+    }
+
+    /// Runs every action that is due, returns when it has to run again or None when there is nothing left to do
+    pub fn run(&mut self, actions: &mut VecDeque<Action>) -> Option<Instant> {
+        self.release_due_keys();
+
+        while let Some(action) = actions.pop_front() {
+            debug!("Check action type {:?}", action);
+
+            match action {
+                Action::Sequence(mut vector) => {
+                    if vector.is_empty() {
+                        continue;
+                    }
+                    let action_in_sequence = vector.remove(0);
+                    if !vector.is_empty() {
+                        actions.push_front(Action::Sequence(vector));
+                    }
+                    actions.push_front(action_in_sequence);
+                },
+                Action::AtomicSequence(vector) => {
+                    for action in vector.iter() {
+                        self.execute(action)
+                    }
+                },
+                Action::Press { hold_ms, .. } | Action::GamepadPress { hold_ms, .. }
+                | Action::MoveStick { duration_ms: Some(hold_ms), .. } | Action::PullTrigger { duration_ms: Some(hold_ms), .. } => {
+                    self.execute(&action);
+                    actions.push_front(Action::WaitUntil(Instant::now().add(Duration::from_millis(hold_ms))));
+                },
+                Action::SmoothMoveMouse { target, duration_ms, easing, tick_rate } => {
+                    let steps = self.smooth_move_steps(target, duration_ms, easing, tick_rate);
+                    actions.push_front(Action::Sequence(steps));
+                },
+                Action::TypeText(text, delay_ms) => {
+                    let mut characters = text.chars();
+                    if let Some(character) = characters.next() {
+                        self.input_system.type_char(character);
+                    }
+                    let rest = characters.as_str();
+                    if !rest.is_empty() {
+                        actions.push_front(Action::Sequence(vec![Action::WaitFor(delay_ms), Action::TypeText(s!(rest), delay_ms)]));
+                    }
+                },
+                Action::WaitFor(ms) => {
+                    actions.push_front(Action::WaitUntil(Instant::now().add(Duration::from_millis(ms))));
+                },
+                Action::WaitUntil(until) => {
+                    if until > Instant::now() {
+                        actions.push_front(action);
+                        break;
+                    }
+                },
+                executable_action => self.execute(&executable_action)
+            };
+        }
+
         if actions.is_empty() {
-            return;
+            self.released_by_pause.clear();
         }
-        let action = actions.remove(0);
-        debug!("Check action type {:?}", action);
+        let next_action = match actions.front() {
+            Some(Action::WaitUntil(until)) => Some(*until),
+            _ => None
+        };
+        next_action.into_iter().chain(self.next_release()).min()
+    }
+
+    /// Reason why the queue must not run actions right now, None when it can
+    pub fn pause_reason(&self) -> Option<String> {
+        self.pause_conditions.pause_reason(self.state_input())
+            .or_else(|| self.window_guard_reason(WindowPolicy::Hold))
+    }
+
+    /// Reason why the queued actions must be dropped instead of executed, None when they can run
+    pub fn drop_reason(&self) -> Option<String> {
+        self.window_guard_reason(WindowPolicy::Drop)
+    }
+
+    fn window_guard_reason(&self, policy: WindowPolicy) -> Option<String> {
+        self.window_guard.as_ref()
+            .filter(|window_guard| window_guard.policy() == policy)
+            .and_then(|window_guard| window_guard.check(self.state_input()))
+    }
+
+    pub fn set_paused_by_command(&mut self, paused: bool) {
+        self.pause_conditions.set_paused_by_command(paused);
+    }
 
+    fn execute(&mut self, action: &Action) {
+        debug!("Executing {:?}", action);
         match action {
-            Action::Sequence(mut vector) => {
-                let action_in_sequence = vector.remove(0);
-                actions.insert(0, action_in_sequence);
-                if vector.len() > 0 {
-                    actions.insert(1, Action::Sequence(vector));
-                };
+            Action::KeyRawDown(raw) => self.key_down(*raw),
+            Action::KeyRawUp(raw) => self.release(HeldInput::Key(*raw)),
+            Action::Press { keys, hold_ms } => {
+                for raw in keys.iter() {
+                    self.key_down(*raw);
+                }
+                self.release_after(*hold_ms, keys.iter().map(|raw| HeldInput::Key(*raw)).collect());
+            },
+            Action::MoveMouseOf(x, y) => self.input_system.move_mouse_of(*x, *y),
+            Action::MoveMouseTo(x, y) => self.input_system.move_mouse_to(*x, *y),
+            Action::MoveMouseToNormalized(x, y) => self.input_system.move_mouse_to_normalized(*x, *y),
+            Action::MouseDown(button) => self.mouse_down(*button),
+            Action::MouseUp(button) => self.release(HeldInput::MouseButton(*button)),
+            Action::MouseClick(button) => {
+                self.input_system.mouse_down(*button);
+                self.input_system.mouse_up(*button);
             },
-            Action::AtomicSequence(vector) => {
-                for action in vector.iter() {
-                    self.execute(action)
+            Action::ScrollOf(x, y) => self.input_system.scroll(*x, *y),
+            Action::GamepadButtonDown(button) => self.gamepad_input(HeldInput::GamepadButton(*button), |gamepad| gamepad.button_down(*button)),
+            Action::GamepadButtonUp(button) => self.release(HeldInput::GamepadButton(*button)),
+            Action::GamepadPress { buttons, hold_ms } => {
+                for button in buttons.iter() {
+                    self.gamepad_input(HeldInput::GamepadButton(*button), |gamepad| gamepad.button_down(*button));
                 }
+                self.release_after(*hold_ms, buttons.iter().map(|button| HeldInput::GamepadButton(*button)).collect());
             },
-            Action::WaitFor(ms) => {
-                actions.insert(0, Action::WaitUntil(Instant::now().add(Duration::from_millis(ms))));
+            Action::MoveStick { stick, x, y, duration_ms } => {
+                if *x == 0.0 && *y == 0.0 {
+                    self.release(HeldInput::GamepadStick(*stick));
+                } else {
+                    self.gamepad_input(HeldInput::GamepadStick(*stick), |gamepad| gamepad.move_stick(*stick, *x, *y));
+                }
+                if let Some(duration_ms) = duration_ms {
+                    self.release_after(*duration_ms, vec![HeldInput::GamepadStick(*stick)]);
+                }
             },
-            Action::WaitUntil(until) => {
-                if until > Instant::now() {
-                    actions.insert(0, action);
+            Action::PullTrigger { trigger, value, duration_ms } => {
+                if *value == 0.0 {
+                    self.release(HeldInput::GamepadTrigger(*trigger));
+                } else {
+                    self.gamepad_input(HeldInput::GamepadTrigger(*trigger), |gamepad| gamepad.pull_trigger(*trigger, *value));
+                }
+                if let Some(duration_ms) = duration_ms {
+                    self.release_after(*duration_ms, vec![HeldInput::GamepadTrigger(*trigger)]);
                 }
             },
-            executable_action => self.execute(&executable_action)
+            Action::TypeText(text, _) => text.chars().for_each(|character| self.input_system.type_char(character)),
+            non_executable_action => error!("Found wrong action nesting, example AtomicSequence with Sequence as an action {:?}", non_executable_action)
+        }
+    }
+
+    /// Movement split in single moves separated by waits, so it doesn't block the queue and it can be cleared
+    fn smooth_move_steps(&self, target: MouseTarget, duration_ms: u64, easing: Easing, tick_rate: u32) -> Vec<Action> {
+        let moves = match target {
+            MouseTarget::Relative(x, y) => {
+                let positions = interpolate((0, 0), (x, y), duration_ms, easing, tick_rate);
+                let mut previous = (0, 0);
+                positions.into_iter()
+                    .map(|position| {
+                        let step = Action::MoveMouseOf(position.0 - previous.0, position.1 - previous.1);
+                        previous = position;
+                        step
+                    })
+                    .collect::<Vec<Action>>()
+            },
+            MouseTarget::Absolute(x, y) => match self.input_system.mouse_position() {
+                Some(from) => interpolate(from, (x, y), duration_ms, easing, tick_rate).into_iter()
+                    .map(|(x, y)| Action::MoveMouseTo(x, y))
+                    .collect::<Vec<Action>>(),
+                None => {
+                    warn!("Mouse position unknown, moving to {}x{} without interpolation", x, y);
+                    vec![Action::MoveMouseTo(x, y)]
+                }
+            }
         };
+
+        moves.into_iter()
+            .zip(tick_waits(duration_ms, tick_rate))
+            .flat_map(|(step, wait)| vec![Action::WaitFor(wait), step])
+            .collect()
     }
 
-    pub fn can_handle(&mut self) -> bool {
-        !self.input_system.is_mouse_left_down()
+    /// Releases held inputs even when the actions that pressed them are no longer in the queue,
+    /// returns when the next ones are due
+    pub fn release_due_keys(&mut self) -> Option<Instant> {
+        let now = Instant::now();
+        let (due, pending): (Vec<_>, Vec<_>) = self.pending_releases.drain(..).partition(|(until, _)| *until <= now);
+        self.pending_releases = pending;
+
+        for (_, inputs) in due {
+            for input in inputs.into_iter().rev() {
+                self.release(input);
+            }
+        }
+        self.next_release()
     }
 
-    fn execute(&mut self, action: &Action) {
-        debug!("Executing {:?}", action);
-        match action {
-            Action::KeyRawDown(raw) => self.input_system.key_down(*raw),
-            Action::KeyRawUp(raw) => self.input_system.key_up(*raw),
-            Action::MoveMouseOf(x, y) => self.input_system.move_mouse_of(*x, *y),
-            non_executable_action => error!("Found wrong action nesting, example AtomicSequence with Sequence as an action {:?}", non_executable_action)
+    fn next_release(&self) -> Option<Instant> {
+        self.pending_releases.iter().map(|(until, _)| *until).min()
+    }
+
+    fn release_after(&mut self, hold_ms: u64, inputs: Vec<HeldInput>) {
+        self.pending_releases.push((Instant::now().add(Duration::from_millis(hold_ms)), inputs));
+    }
+
+    /// Releases every key, mouse button and gamepad input still held, latest pressed first
+    pub fn release_all(&mut self) {
+        self.pending_releases.clear();
+        self.released_by_pause.clear();
+        let held_inputs = std::mem::take(&mut self.held_inputs);
+        if !held_inputs.is_empty() {
+            info!("Releasing held inputs {:?}", held_inputs);
+        }
+
+        for held_input in held_inputs.into_iter().rev() {
+            self.send_release(held_input);
+        }
+    }
+
+    /// Releases what is held while another category runs exclusively, the running action resumes afterwards
+    /// without releasing it a second time
+    pub fn release_for_pause(&mut self) {
+        let held_inputs = std::mem::take(&mut self.held_inputs);
+        if !held_inputs.is_empty() {
+            info!("Releasing held inputs {:?} until resumed", held_inputs);
+        }
+
+        for held_input in held_inputs.into_iter().rev() {
+            self.send_release(held_input);
+            self.released_by_pause.push(held_input);
+        }
+    }
+
+    fn release(&mut self, input: HeldInput) {
+        if let Some(index) = self.released_by_pause.iter().position(|released| *released == input) {
+            self.released_by_pause.remove(index);
+            return;
+        }
+        self.send_release(input);
+        self.held_inputs.retain(|held_input| *held_input != input);
+    }
+
+    fn send_release(&mut self, input: HeldInput) {
+        match input {
+            HeldInput::Key(raw) => self.input_system.key_up(raw),
+            HeldInput::MouseButton(button) => self.input_system.mouse_up(button),
+            HeldInput::GamepadButton(button) => self.with_gamepad(|gamepad| gamepad.button_up(button)),
+            HeldInput::GamepadStick(stick) => self.with_gamepad(|gamepad| gamepad.move_stick(stick, 0.0, 0.0)),
+            HeldInput::GamepadTrigger(trigger) => self.with_gamepad(|gamepad| gamepad.pull_trigger(trigger, 0.0))
+        }
+    }
+
+    fn gamepad_input(&mut self, input: HeldInput, send: impl FnOnce(&mut dyn GamepadOutput)) {
+        self.with_gamepad(send);
+        self.hold(input);
+    }
+
+    fn with_gamepad(&self, send: impl FnOnce(&mut dyn GamepadOutput)) {
+        match &self.gamepad {
+            Some(gamepad) => send(gamepad.lock().unwrap().as_mut()),
+            None => error!("Gamepad action without a gamepad, set the gamepad backend in the configuration")
+        }
+    }
+
+    fn key_down(&mut self, raw: u16) {
+        self.input_system.key_down(raw);
+        self.hold(HeldInput::Key(raw));
+    }
+
+    fn mouse_down(&mut self, button: MouseButton) {
+        self.input_system.mouse_down(button);
+        self.hold(HeldInput::MouseButton(button));
+    }
+
+    fn hold(&mut self, input: HeldInput) {
+        if !self.held_inputs.contains(&input) {
+            self.held_inputs.push(input);
+        }
+    }
+}
+
+/// Dropped on shutdown, Ctrl-C and panics unwinding through the queues, nothing must stay pressed
+impl Drop for ActionHandler {
+    fn drop(&mut self) {
+        self.release_all();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::actions::action::ActionCategory;
+    use crate::event_to_action::configurable_event_to_action::configurable_event_to_action::ConfigurableEventToAction;
+    use crate::event_to_action::event_to_action::EventToAction;
+    use crate::stream_interface::events::{ChatEvent, ChatMessage};
+    use crate::system_input::recording::recording_system_input::{RecordingSystemInput, RecordedInput, Timeline};
+    use crate::system_input::system_input::MouseButton;
+    use crate::system_input::gamepad_output::shared_gamepad;
+    use crate::system_input::memory::memory_gamepad_output::{MemoryGamepadOutput, GamepadEvent, GamepadLog};
+    use crate::utils::app_config::{Mapping, MappingConfig};
+    use std::convert::TryInto;
+
+    #[test]
+    fn chat_events_produce_exact_input_timeline() {
+        let timeline = Timeline::default();
+        let mut handler = ActionHandler::new(Box::new(RecordingSystemInput::new(timeline.clone())), PauseConditions::default(), None, None);
+        let mut event_to_action = ConfigurableEventToAction::new(Mapping { config: vec![
+            message_mapping("up", vec!["kd:Up", "w5", "ku:Up"]),
+            message_mapping("jump", vec!["kp:Space:5"]),
+            message_mapping("shoot", vec!["mc:Left", "ms0x-1"])
+        ] }.try_into().unwrap());
+
+        let actions = ["up", "nothing", "jump", "shoot"].iter()
+            .filter_map(|message| event_to_action.execute(message_event(message)))
+            .map(|triggered| match triggered.category { ActionCategory::Uncategorized(action) | ActionCategory::WithCategory(_, action) => action })
+            .collect::<Vec<Action>>();
+        run_until_done(&mut handler, actions);
+
+        assert_eq!(timeline.inputs(), vec![
+            RecordedInput::KeyDown(38), RecordedInput::KeyUp(38),
+            RecordedInput::KeyDown(32), RecordedInput::KeyUp(32),
+            RecordedInput::MouseDown(MouseButton::Left), RecordedInput::MouseUp(MouseButton::Left), RecordedInput::Scroll(0, -1)
+        ]);
+    }
+
+    #[test]
+    fn press_releases_keys_in_reverse_order_after_hold() {
+        let timeline = Timeline::default();
+        let mut handler = ActionHandler::new(Box::new(RecordingSystemInput::new(timeline.clone())), PauseConditions::default(), None, None);
+
+        run_until_done(&mut handler, vec![Action::Press { keys: vec![17, 67], hold_ms: 5 }]);
+
+        let entries = timeline.entries();
+        assert_eq!(timeline.inputs(), vec![RecordedInput::KeyDown(17), RecordedInput::KeyDown(67), RecordedInput::KeyUp(67), RecordedInput::KeyUp(17)]);
+        assert!(entries[2].0 - entries[1].0 >= Duration::from_millis(5));
+    }
+
+    #[test]
+    fn release_all_releases_held_keys_and_buttons_latest_first() {
+        let timeline = Timeline::default();
+        let mut handler = ActionHandler::new(Box::new(RecordingSystemInput::new(timeline.clone())), PauseConditions::default(), None, None);
+
+        handler.run(&mut VecDeque::from(vec![Action::AtomicSequence(vec![
+            Action::KeyRawDown(38), Action::KeyRawDown(40), Action::KeyRawUp(38), Action::MouseDown(MouseButton::Right)
+        ])]));
+        handler.release_all();
+        handler.release_all();
+
+        assert_eq!(timeline.inputs()[4..].to_vec(), vec![RecordedInput::MouseUp(MouseButton::Right), RecordedInput::KeyUp(40)]);
+    }
+
+    #[test]
+    fn press_released_when_handler_dropped_mid_hold() {
+        let timeline = Timeline::default();
+        let mut handler = ActionHandler::new(Box::new(RecordingSystemInput::new(timeline.clone())), PauseConditions::default(), None, None);
+
+        handler.run(&mut VecDeque::from(vec![Action::Press { keys: vec![38], hold_ms: 60_000 }]));
+        drop(handler);
+
+        assert_eq!(timeline.inputs(), vec![RecordedInput::KeyDown(38), RecordedInput::KeyUp(38)]);
+    }
+
+    #[test]
+    fn gamepad_press_and_stick_movement_released_after_duration() {
+        let log = GamepadLog::default();
+        let gamepad = shared_gamepad(Box::new(MemoryGamepadOutput::new(log.clone())));
+        let mut handler = ActionHandler::new(Box::new(RecordingSystemInput::new(Timeline::default())), PauseConditions::default(), None, Some(gamepad));
+
+        run_until_done(&mut handler, vec![
+            Action::GamepadPress { buttons: vec![GamepadButton::A], hold_ms: 5 },
+            Action::MoveStick { stick: GamepadStick::Left, x: 0.5, y: -1.0, duration_ms: Some(5) }
+        ]);
+
+        assert_eq!(log.events(), vec![
+            GamepadEvent::ButtonDown(GamepadButton::A), GamepadEvent::ButtonUp(GamepadButton::A),
+            GamepadEvent::Stick(GamepadStick::Left, 0.5, -1.0), GamepadEvent::Stick(GamepadStick::Left, 0.0, 0.0)
+        ]);
+    }
+
+    #[test]
+    fn release_all_centers_sticks_and_releases_gamepad_buttons_and_triggers() {
+        let log = GamepadLog::default();
+        let gamepad = shared_gamepad(Box::new(MemoryGamepadOutput::new(log.clone())));
+        let mut handler = ActionHandler::new(Box::new(RecordingSystemInput::new(Timeline::default())), PauseConditions::default(), None, Some(gamepad));
+
+        handler.run(&mut VecDeque::from(vec![Action::AtomicSequence(vec![
+            Action::GamepadButtonDown(GamepadButton::DpadUp),
+            Action::MoveStick { stick: GamepadStick::Right, x: 1.0, y: 0.0, duration_ms: None },
+            Action::PullTrigger { trigger: GamepadTrigger::Left, value: 0.5, duration_ms: None }
+        ])]));
+        handler.release_all();
+
+        assert_eq!(log.events()[3..].to_vec(), vec![
+            GamepadEvent::Trigger(GamepadTrigger::Left, 0.0), GamepadEvent::Stick(GamepadStick::Right, 0.0, 0.0), GamepadEvent::ButtonUp(GamepadButton::DpadUp)
+        ]);
+    }
+
+    fn run_until_done(handler: &mut ActionHandler, actions: Vec<Action>) {
+        let mut actions = VecDeque::from(actions);
+        while let Some(wake_up_at) = handler.run(&mut actions) {
+            std::thread::sleep(wake_up_at.saturating_duration_since(Instant::now()));
         }
     }
+
+    fn message_mapping(id: &str, actions: Vec<&str>) -> MappingConfig {
+        MappingConfig { source: s!("message"), id: s!(id), actions: actions.into_iter().map(|action| s!(action)).collect(), name: s!(""), category: s!(""), comparison: s!(""), limit: None, text: None, priority: 0, preempt: s!("") }
+    }
+
+    fn message_event(content: &str) -> ChatEvent {
+        ChatEvent::Message(ChatMessage { name: s!(""), content: s!(content), is_mod: false, is_broadcaster: false, is_subscriber: false })
+    }
 }
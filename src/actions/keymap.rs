@@ -0,0 +1,30 @@
+/// Symbolic key names available in the actions DSL, e.g. `kd:Up` or `kp:LeftShift+W`.
+/// Codes are Windows virtual-key codes (the same as JavaScript key codes), every SystemInput
+/// backend is responsible to translate them into its native codes.
+const KEYMAP: &[(&str, u16)] = &[
+    ("Backspace", 8), ("Tab", 9), ("Enter", 13), ("Shift", 16), ("Ctrl", 17), ("Alt", 18), ("Pause", 19),
+    ("CapsLock", 20), ("Escape", 27), ("Space", 32), ("PageUp", 33), ("PageDown", 34), ("End", 35), ("Home", 36),
+    ("Left", 37), ("Up", 38), ("Right", 39), ("Down", 40), ("PrintScreen", 44), ("Insert", 45), ("Delete", 46),
+    ("0", 48), ("1", 49), ("2", 50), ("3", 51), ("4", 52), ("5", 53), ("6", 54), ("7", 55), ("8", 56), ("9", 57),
+    ("A", 65), ("B", 66), ("C", 67), ("D", 68), ("E", 69), ("F", 70), ("G", 71), ("H", 72), ("I", 73), ("J", 74),
+    ("K", 75), ("L", 76), ("M", 77), ("N", 78), ("O", 79), ("P", 80), ("Q", 81), ("R", 82), ("S", 83), ("T", 84),
+    ("U", 85), ("V", 86), ("W", 87), ("X", 88), ("Y", 89), ("Z", 90), ("LeftMeta", 91), ("RightMeta", 92),
+    ("Numpad0", 96), ("Numpad1", 97), ("Numpad2", 98), ("Numpad3", 99), ("Numpad4", 100), ("Numpad5", 101),
+    ("Numpad6", 102), ("Numpad7", 103), ("Numpad8", 104), ("Numpad9", 105), ("NumpadMultiply", 106),
+    ("NumpadAdd", 107), ("NumpadSubtract", 109), ("NumpadDecimal", 110), ("NumpadDivide", 111),
+    ("F1", 112), ("F2", 113), ("F3", 114), ("F4", 115), ("F5", 116), ("F6", 117), ("F7", 118), ("F8", 119),
+    ("F9", 120), ("F10", 121), ("F11", 122), ("F12", 123), ("F13", 124), ("F14", 125), ("F15", 126), ("F16", 127),
+    ("F17", 128), ("F18", 129), ("F19", 130), ("F20", 131), ("F21", 132), ("F22", 133), ("F23", 134), ("F24", 135),
+    ("NumLock", 144), ("ScrollLock", 145), ("LeftShift", 160), ("RightShift", 161), ("LeftCtrl", 162),
+    ("RightCtrl", 163), ("LeftAlt", 164), ("RightAlt", 165), ("Semicolon", 186), ("Equal", 187), ("Comma", 188),
+    ("Minus", 189), ("Period", 190), ("Slash", 191), ("Backquote", 192), ("BracketLeft", 219), ("Backslash", 220),
+    ("BracketRight", 221), ("Quote", 222)
+];
+
+/// Key code from a symbolic name (case insensitive), or from a numeric key code when no name matches: `1` is the key 1
+pub fn key_code(key: &str) -> Option<u16> {
+    KEYMAP.iter()
+        .find(|(name, _)| name.eq_ignore_ascii_case(key))
+        .map(|(_, code)| *code)
+        .or_else(|| key.parse::<u16>().ok())
+}
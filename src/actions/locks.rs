@@ -0,0 +1,246 @@
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex};
+use tokio::sync::watch;
+use crate::utils::app_config::CategoryQueueConfig;
+use crate::{s};
+
+#[derive(Default)]
+struct LockState {
+    /// Category running an exclusive action or waiting for the others to step aside to run it
+    exclusive: Option<String>,
+    /// Named lock and the category holding it
+    held: HashMap<String, String>,
+    /// Categories running an action
+    busy: HashSet<String>,
+    /// Busy categories stopped at a step boundary, released, until the exclusive action ends
+    stepped_aside: HashSet<String>
+}
+
+/// Locks shared by every category queue, the queues are notified when they are taken or released
+#[derive(Clone)]
+pub struct SharedLocks {
+    state: Arc<Mutex<LockState>>,
+    changes: Arc<watch::Sender<()>>,
+    listener: watch::Receiver<()>
+}
+
+impl Default for SharedLocks {
+    fn default() -> Self {
+        let (changes, listener) = watch::channel(());
+        SharedLocks { state: Arc::new(Mutex::new(LockState::default())), changes: Arc::new(changes), listener }
+    }
+}
+
+impl SharedLocks {
+    fn notify(&self) {
+        // Nobody listening is fine, it means no queue is waiting
+        let _ = self.changes.send(());
+    }
+}
+
+/// Exclusivity and named locks of a category queue. They are taken when an action starts and released when it ends
+pub struct CategoryLocks {
+    category: String,
+    exclusive: bool,
+    names: Vec<String>,
+    shared: SharedLocks,
+    listener: watch::Receiver<()>,
+    holding: bool
+}
+
+/// Locks of a category as written in the configuration
+pub fn category_locks_birth(category: &str, queue: &CategoryQueueConfig, shared: &SharedLocks) -> Result<CategoryLocks, String> {
+    if queue.locks.iter().any(|name| name.is_empty()) {
+        return Err(format!("Provided empty lock name for category {}", category));
+    }
+    Ok(CategoryLocks {
+        category: s!(category),
+        exclusive: queue.exclusive,
+        names: queue.locks.clone(),
+        shared: shared.clone(),
+        listener: shared.listener.clone(),
+        holding: false
+    })
+}
+
+impl CategoryLocks {
+    /// Reason why the queue must stop between steps, another category running an exclusive action
+    pub fn pause_reason(&self) -> Option<String> {
+        let state = self.shared.state.lock().unwrap();
+        match &state.exclusive {
+            Some(category) if *category != self.category => Some(format!("category {} running exclusively", category)),
+            _ => None
+        }
+    }
+
+    /// Takes the locks to start an action, false when another category holds one of them.
+    /// An exclusive category asks for the exclusivity and starts once every other busy category stepped aside
+    pub fn acquire(&mut self) -> bool {
+        if self.holding {
+            return true;
+        }
+
+        let mut state = self.shared.state.lock().unwrap();
+        let exclusive_taken = self.exclusive && matches!(&state.exclusive, Some(category) if *category != self.category);
+        let name_taken = self.names.iter().any(|name| state.held.contains_key(name));
+        if exclusive_taken || name_taken {
+            return false;
+        }
+
+        if self.exclusive {
+            let asking = state.exclusive.is_none();
+            state.exclusive = Some(self.category.clone());
+            let others_running = state.busy.iter().any(|category| *category != self.category && !state.stepped_aside.contains(category));
+            if others_running {
+                drop(state);
+                if asking {
+                    // The other queues have to stop at their next step
+                    self.shared.notify();
+                }
+                return false;
+            }
+        }
+        for name in &self.names {
+            state.held.insert(name.clone(), self.category.clone());
+        }
+        state.busy.insert(self.category.clone());
+        drop(state);
+        self.holding = true;
+        if self.exclusive {
+            self.shared.notify();
+        }
+        true
+    }
+
+    /// Another category runs exclusively, the action of this one stays stopped without holding anything until it ends
+    pub fn step_aside(&mut self) {
+        let mut state = self.shared.state.lock().unwrap();
+        let stepped_aside = state.busy.contains(&self.category) && state.stepped_aside.insert(self.category.clone());
+        drop(state);
+        if stepped_aside {
+            self.shared.notify();
+        }
+    }
+
+    /// Gives up the exclusivity asked when nothing is left to run, the other categories resume
+    pub fn stop_waiting(&mut self) {
+        if self.holding {
+            return;
+        }
+        let mut state = self.shared.state.lock().unwrap();
+        if state.exclusive.as_ref() == Some(&self.category) {
+            state.exclusive = None;
+            state.stepped_aside.clear();
+            drop(state);
+            self.shared.notify();
+        }
+    }
+
+    pub fn release(&mut self) {
+        if !self.holding {
+            return;
+        }
+
+        let mut state = self.shared.state.lock().unwrap();
+        if state.exclusive.as_ref() == Some(&self.category) {
+            state.exclusive = None;
+            state.stepped_aside.clear();
+        }
+        state.held.retain(|_, holder| *holder != self.category);
+        state.busy.remove(&self.category);
+        drop(state);
+        self.holding = false;
+        self.shared.notify();
+    }
+
+    /// Resolves when a category took or released its locks
+    pub async fn changed(&mut self) {
+        if self.listener.changed().await.is_err() {
+            // Can't happen while this category holds the shared locks, never resolve instead of spinning
+            futures::future::pending::<()>().await;
+        }
+    }
+}
+
+impl Drop for CategoryLocks {
+    fn drop(&mut self) {
+        self.release();
+        self.stop_waiting();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn exclusive_category_running_pauses_the_others() {
+        let shared = SharedLocks::default();
+        let mut menu = category_locks_birth("menu", &CategoryQueueConfig { exclusive: true, ..Default::default() }, &shared).unwrap();
+        let mut other_exclusive = category_locks_birth("chat", &CategoryQueueConfig { exclusive: true, ..Default::default() }, &shared).unwrap();
+        let movement = category_locks_birth("movement", &CategoryQueueConfig::default(), &shared).unwrap();
+
+        assert!(menu.acquire());
+        assert_eq!(movement.pause_reason(), Some(s!("category menu running exclusively")));
+        assert_eq!(menu.pause_reason(), None);
+        assert!(!other_exclusive.acquire());
+
+        menu.release();
+        assert_eq!(movement.pause_reason(), None);
+        assert!(other_exclusive.acquire());
+    }
+
+    #[test]
+    fn exclusive_category_waits_for_the_busy_ones_to_step_aside() {
+        let shared = SharedLocks::default();
+        let mut menu = category_locks_birth("menu", &CategoryQueueConfig { exclusive: true, ..Default::default() }, &shared).unwrap();
+        let mut movement = category_locks_birth("movement", &CategoryQueueConfig::default(), &shared).unwrap();
+        let mut camera = category_locks_birth("camera", &CategoryQueueConfig::default(), &shared).unwrap();
+
+        assert!(movement.acquire());
+        assert!(!menu.acquire());
+        assert_eq!(movement.pause_reason(), Some(s!("category menu running exclusively")));
+        assert_eq!(camera.pause_reason(), Some(s!("category menu running exclusively")));
+        camera.step_aside();
+        assert!(!menu.acquire());
+
+        movement.step_aside();
+        assert!(menu.acquire());
+        menu.release();
+        assert_eq!(movement.pause_reason(), None);
+        assert!(!menu.acquire());
+        menu.stop_waiting();
+        assert_eq!(movement.pause_reason(), None);
+    }
+
+    #[test]
+    fn named_lock_held_by_one_category_at_a_time() {
+        let shared = SharedLocks::default();
+        let mut inventory = category_locks_birth("inventory", &CategoryQueueConfig { locks: vec![s!("hands")], ..Default::default() }, &shared).unwrap();
+        let mut attack = category_locks_birth("attack", &CategoryQueueConfig { locks: vec![s!("hands"), s!("mouse")], ..Default::default() }, &shared).unwrap();
+        let mut camera = category_locks_birth("camera", &CategoryQueueConfig { locks: vec![s!("mouse")], ..Default::default() }, &shared).unwrap();
+
+        assert!(inventory.acquire());
+        assert!(!attack.acquire());
+        assert!(camera.acquire());
+        assert_eq!(attack.pause_reason(), None);
+
+        inventory.release();
+        camera.release();
+        assert!(attack.acquire());
+    }
+
+    #[tokio::test]
+    async fn release_notifies_the_waiting_queues() {
+        let shared = SharedLocks::default();
+        let mut menu = category_locks_birth("menu", &CategoryQueueConfig { exclusive: true, ..Default::default() }, &shared).unwrap();
+        let mut movement = category_locks_birth("movement", &CategoryQueueConfig::default(), &shared).unwrap();
+        menu.acquire();
+        movement.changed().await;
+
+        drop(menu);
+
+        movement.changed().await;
+        assert_eq!(movement.pause_reason(), None);
+    }
+}
@@ -1,3 +1,10 @@
 pub mod action;
 pub mod handler;
 pub mod queue;
+pub mod keymap;
+pub mod movement;
+pub mod pause;
+pub mod window_guard;
+pub mod queue_policy;
+pub mod locks;
+pub mod queue_factory;
@@ -0,0 +1,88 @@
+#[derive(Debug)]
+#[derive(Clone, Copy)]
+#[derive(PartialEq)]
+pub enum Easing {
+    Linear,
+    EaseIn,
+    EaseOut,
+    EaseInOut
+}
+
+impl Easing {
+    fn apply(&self, t: f64) -> f64 {
+        match self {
+            Easing::Linear => t,
+            Easing::EaseIn => t * t,
+            Easing::EaseOut => 1.0 - (1.0 - t) * (1.0 - t),
+            Easing::EaseInOut => if t < 0.5 { 2.0 * t * t } else { 1.0 - (-2.0 * t + 2.0).powi(2) / 2.0 }
+        }
+    }
+}
+
+#[derive(Debug)]
+#[derive(Clone, Copy)]
+#[derive(PartialEq)]
+pub enum MouseTarget {
+    Relative(i32, i32),
+    Absolute(i32, i32)
+}
+
+/// Positions the mouse passes through going from `from` to `to`, one per tick, the last one is always `to`
+pub fn interpolate(from: (i32, i32), to: (i32, i32), duration_ms: u64, easing: Easing, tick_rate: u32) -> Vec<(i32, i32)> {
+    let ticks = (duration_ms * tick_rate as u64 / 1000).max(1);
+
+    (1..=ticks)
+        .map(|tick| {
+            let progress = easing.apply(tick as f64 / ticks as f64);
+            (
+                from.0 + ((to.0 - from.0) as f64 * progress).round() as i32,
+                from.1 + ((to.1 - from.1) as f64 * progress).round() as i32
+            )
+        })
+        .collect()
+}
+
+/// Milliseconds waited before each tick of a movement, each tick is due at its offset from the start
+/// so the waits add up to the whole duration
+pub fn tick_waits(duration_ms: u64, tick_rate: u32) -> Vec<u64> {
+    let ticks = (duration_ms * tick_rate as u64 / 1000).max(1);
+    let offset = |tick: u64| duration_ms * tick / ticks;
+
+    (1..=ticks).map(|tick| offset(tick) - offset(tick - 1)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn interpolate_linear_spreads_delta_evenly_across_ticks() {
+        assert_eq!(interpolate((0, 0), (100, -50), 100, Easing::Linear, 40), vec![(25, -13), (50, -25), (75, -38), (100, -50)]);
+    }
+
+    #[test]
+    fn interpolate_always_ends_on_target() {
+        for easing in [Easing::Linear, Easing::EaseIn, Easing::EaseOut, Easing::EaseInOut].iter() {
+            assert_eq!(interpolate((10, 10), (333, 77), 250, *easing, 60).last(), Some(&(333, 77)));
+        }
+    }
+
+    #[test]
+    fn interpolate_duration_shorter_than_tick_moves_in_one_step() {
+        assert_eq!(interpolate((0, 0), (10, 10), 5, Easing::EaseIn, 60), vec![(10, 10)]);
+    }
+
+    #[test]
+    fn tick_waits_add_up_to_the_duration() {
+        assert_eq!(tick_waits(100, 30), vec![33, 33, 34]);
+        assert_eq!(tick_waits(250, 60).iter().sum::<u64>(), 250);
+        assert_eq!(tick_waits(5, 60), vec![5]);
+    }
+
+    #[test]
+    fn interpolate_ease_in_moves_less_at_the_beginning() {
+        let positions = interpolate((0, 0), (100, 0), 1000, Easing::EaseIn, 10);
+        assert!(positions[0].0 < 10);
+        assert!(positions[9].0 - positions[8].0 > 10);
+    }
+}
@@ -0,0 +1,148 @@
+use std::path::PathBuf;
+use std::time::Duration;
+use tokio::sync::broadcast::Sender;
+use tokio::time::sleep;
+use crate::actions::queue::{QueueControl, QueueCommand};
+use crate::system_input::system_input::SystemInput;
+use crate::{s};
+
+const HOTKEY_POLL_MS: u64 = 50;
+
+#[derive(Debug)]
+#[derive(Clone)]
+#[derive(PartialEq)]
+pub enum PauseCondition {
+    MouseLeftDown,
+    RecentInput(Duration),
+    File(PathBuf)
+}
+
+impl PauseCondition {
+    /// The condition reads the mouse or the keyboard of the PC, the file one doesn't
+    fn reads_input_state(&self) -> bool {
+        !matches!(self, PauseCondition::File(_))
+    }
+
+    /// Reason of the pause when the condition is met
+    fn check(&self, input_system: &dyn SystemInput) -> Option<String> {
+        match self {
+            PauseCondition::MouseLeftDown if input_system.is_mouse_left_down() => Some(s!("mouse left button down")),
+            PauseCondition::RecentInput(within) => match input_system.idle_time() {
+                Some(idle) if idle < *within => Some(format!("input on the PC in the last {}ms", within.as_millis())),
+                _ => None
+            },
+            PauseCondition::File(path) if path.exists() => Some(format!("file {} exists", path.display())),
+            _ => None
+        }
+    }
+}
+
+/// Condition written as `mouse_left_down`, `recent_input:MS` or `file:PATH`
+pub fn pause_condition_birth(condition_to_map: &str) -> Result<PauseCondition, String> {
+    match condition_to_map {
+        "mouse_left_down" => Ok(PauseCondition::MouseLeftDown),
+        recent_input if recent_input.starts_with("recent_input:") => recent_input["recent_input:".len()..].parse::<u64>()
+            .map(|ms| PauseCondition::RecentInput(Duration::from_millis(ms)))
+            .map_err(|_| format!("Provided wrong pause condition {}", recent_input)),
+        file if file.starts_with("file:") => Ok(PauseCondition::File(PathBuf::from(&file["file:".len()..]))),
+        wrong_condition => Err(format!("Provided wrong pause condition {}", wrong_condition))
+    }
+}
+
+/// Conditions of a category as written in the configuration
+pub fn pause_conditions_birth(conditions_to_map: &[String]) -> Result<PauseConditions, String> {
+    Ok(PauseConditions::new(conditions_to_map.iter().map(|condition| pause_condition_birth(condition)).collect::<Result<_, String>>()?))
+}
+
+#[derive(Default)]
+pub struct PauseConditions {
+    conditions: Vec<PauseCondition>,
+    paused_by_command: bool
+}
+
+impl PauseConditions {
+    pub fn new(conditions: Vec<PauseCondition>) -> Self {
+        PauseConditions { conditions, paused_by_command: false }
+    }
+
+    pub fn reads_input_state(&self) -> bool {
+        self.conditions.iter().any(PauseCondition::reads_input_state)
+    }
+
+    pub fn set_paused_by_command(&mut self, paused: bool) {
+        self.paused_by_command = paused;
+    }
+
+    pub fn pause_reason(&self, input_system: &dyn SystemInput) -> Option<String> {
+        if self.paused_by_command {
+            return Some(s!("paused by command"));
+        }
+        self.conditions.iter().find_map(|condition| condition.check(input_system))
+    }
+}
+
+/// Pauses every queue when the hotkey is pressed, resumes them when it's pressed again
+pub async fn toggle_pause_on_hotkey(raw: u16, input_system: Box<dyn SystemInput>, control_notifier: Sender<QueueControl>) {
+    let mut paused = false;
+    let mut was_down = false;
+
+    loop {
+        let is_down = input_system.is_key_down(raw);
+        if is_down && !was_down {
+            paused = !paused;
+            info!("Pause hotkey pressed, {} every queue", if paused { "pausing" } else { "resuming" });
+            let command = if paused { QueueCommand::Pause } else { QueueCommand::Resume };
+            if let Err(e) = control_notifier.send(QueueControl::all(command)) {
+                error!("toggle_pause_on_hotkey::send_error::{}", e);
+            }
+        }
+        was_down = is_down;
+        sleep(Duration::from_millis(HOTKEY_POLL_MS)).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::system_input::recording::recording_system_input::{RecordingSystemInput, Timeline};
+
+    #[test]
+    fn pause_conditions_written_in_config_then_conditions() {
+        assert_eq!(pause_condition_birth("mouse_left_down"), Ok(PauseCondition::MouseLeftDown));
+        assert_eq!(pause_condition_birth("recent_input:2000"), Ok(PauseCondition::RecentInput(Duration::from_millis(2000))));
+        assert_eq!(pause_condition_birth("file:C:/stream/pause"), Ok(PauseCondition::File(PathBuf::from("C:/stream/pause"))));
+    }
+
+    #[test]
+    fn only_file_conditions_then_input_state_not_read() {
+        assert!(!PauseConditions::new(vec![PauseCondition::File(PathBuf::from("pause"))]).reads_input_state());
+        assert!(PauseConditions::new(vec![PauseCondition::File(PathBuf::from("pause")), PauseCondition::MouseLeftDown]).reads_input_state());
+        assert!(PauseConditions::new(vec![PauseCondition::RecentInput(Duration::from_millis(500))]).reads_input_state());
+    }
+
+    #[test]
+    fn paused_by_command_then_paused_until_resumed() {
+        let input_system = RecordingSystemInput::new(Timeline::default());
+        let mut pause_conditions = PauseConditions::new(vec![PauseCondition::MouseLeftDown]);
+
+        assert_eq!(pause_conditions.pause_reason(&input_system), None);
+        pause_conditions.set_paused_by_command(true);
+        assert!(pause_conditions.pause_reason(&input_system).is_some());
+        pause_conditions.set_paused_by_command(false);
+        assert_eq!(pause_conditions.pause_reason(&input_system), None);
+    }
+
+    #[test]
+    fn pause_file_exists_then_paused() {
+        let path = std::env::temp_dir().join("twitch_commands_pause_file_test");
+        let input_system = RecordingSystemInput::new(Timeline::default());
+        let pause_conditions = PauseConditions::new(vec![PauseCondition::File(path.clone())]);
+
+        std::fs::write(&path, "").unwrap();
+        let paused_reason = pause_conditions.pause_reason(&input_system);
+        std::fs::remove_file(&path).unwrap();
+
+        assert!(paused_reason.is_some());
+        assert_eq!(pause_conditions.pause_reason(&input_system), None);
+    }
+}
@@ -1,23 +1,82 @@
-use futures::{join};
-use tokio::sync::mpsc::{channel, Receiver, Sender};
-use tokio::time::{sleep, Duration};
-use std::sync::{Arc, Mutex};
-use std::ops::DerefMut;
-use std::collections::HashMap;
-use crate::actions::action::{Action, ActionCategory};
+use tokio::sync::mpsc::{unbounded_channel, Receiver, UnboundedReceiver, UnboundedSender};
+use tokio::sync::broadcast;
+use tokio::sync::broadcast::error::RecvError;
+use tokio::time::{sleep_until, Duration, Instant};
+use std::collections::{HashMap, VecDeque};
+use std::future::Future;
+use std::pin::Pin;
+use futures::stream::{FuturesUnordered, StreamExt};
+use crate::actions::action::{Action, ActionCategory, QueuedAction, TriggeredActions};
 use crate::actions::handler::ActionHandler;
+use crate::actions::queue_policy::{PendingActions, Overflow, OverflowPolicy};
+use crate::actions::locks::CategoryLocks;
 use crate::{s};
 
-const UNCATEGORIZED_CHANNEL_NAME: &str = "_uncategorized";
+pub const UNCATEGORIZED_CHANNEL_NAME: &str = "_uncategorized";
+const PAUSE_CHECK_INTERVAL_MS: u64 = 100;
 
-pub fn action_queue_coordinators(custom_categories: Vec<String>) -> (HashMap<String, Sender<Action>>, HashMap<String, Receiver<Action>>) {
+#[derive(Debug)]
+#[derive(Clone)]
+#[derive(PartialEq)]
+pub enum QueueCommand {
+    ReleaseAll,
+    Pause,
+    Resume,
+    /// Discards the actions waiting, the running one ends
+    Clear,
+    /// Stops the running action releasing what it holds, the next one starts
+    Skip,
+    /// Stops receiving actions, the waiting ones are discarded and the running one stopped. The queue ends
+    Close
+}
+
+/// Command for the queue of a category, or for every queue when category is None
+#[derive(Debug)]
+#[derive(Clone)]
+#[derive(PartialEq)]
+pub struct QueueControl {
+    pub category: Option<String>,
+    pub command: QueueCommand
+}
+
+impl QueueControl {
+    pub fn all(command: QueueCommand) -> Self {
+        QueueControl { category: None, command }
+    }
+
+    fn applies_to(&self, category_name: &str) -> bool {
+        self.category.is_none() || self.category.as_deref() == Some(category_name)
+    }
+}
+
+/// Paused and resumed transitions of a queue
+#[derive(Debug)]
+#[derive(Clone)]
+#[derive(PartialEq)]
+pub enum QueueEvent {
+    Paused { category: String, reason: String },
+    Resumed { category: String },
+    /// Action of a viewer refused because the queue, or the viewer actions, were at the maximum and the overflow policy is reject
+    Rejected { category: String, user: String, action: Action }
+}
+
+pub fn queue_event_channel() -> (broadcast::Sender<QueueEvent>, broadcast::Receiver<QueueEvent>) {
+    broadcast::channel::<QueueEvent>(100)
+}
+
+pub fn queue_control_channel() -> (broadcast::Sender<QueueControl>, broadcast::Receiver<QueueControl>) {
+    broadcast::channel::<QueueControl>(100)
+}
+
+/// Queue channels are unbounded so the router never waits, each queue applies its own max length
+pub fn action_queue_coordinators(custom_categories: Vec<String>) -> (HashMap<String, UnboundedSender<QueuedAction>>, HashMap<String, UnboundedReceiver<QueuedAction>>) {
     let all_categories = add_uncategorized(custom_categories);
 
     let mut notifiers_hash_map = HashMap::with_capacity(all_categories.len());
     let mut receivers_hash_map = HashMap::with_capacity(all_categories.len());
 
     for category_name in all_categories {
-        let queue = channel::<Action>(100);
+        let queue = unbounded_channel::<QueuedAction>();
         notifiers_hash_map.insert(category_name.clone(), queue.0);
         receivers_hash_map.insert(category_name, queue.1);
     }
@@ -25,8 +84,33 @@ pub fn action_queue_coordinators(custom_categories: Vec<String>) -> (HashMap<Str
     (notifiers_hash_map, receivers_hash_map)
 }
 
-pub async fn redirect_action_in_queue(category_receiver: &mut Receiver<ActionCategory>, queue_notifiers: &mut HashMap<String, Sender<Action>>) {
-    while let Some(category) = category_receiver.recv().await {
+/// Category queue created or retired when the configuration is reloaded
+pub enum QueueChange {
+    Created(String, UnboundedSender<QueuedAction>),
+    /// Dropping its notifier the queue ends once it ran what it received
+    Retired(String)
+}
+
+/// Routes the actions to the category queues. Queue changes go first, a queue created is there before the actions of the new configuration
+pub async fn redirect_action_in_queue(category_receiver: &mut Receiver<TriggeredActions>, mut queue_notifiers: HashMap<String, UnboundedSender<QueuedAction>>, mut queue_changes: UnboundedReceiver<QueueChange>) {
+    let mut changing = true;
+    loop {
+        let triggered = tokio::select! {
+            biased;
+            change = queue_changes.recv(), if changing => {
+                match change {
+                    Some(QueueChange::Created(category_name, sender)) => { queue_notifiers.insert(category_name, sender); },
+                    Some(QueueChange::Retired(category_name)) => { queue_notifiers.remove(&category_name); },
+                    None => changing = false
+                }
+                continue;
+            },
+            triggered = category_receiver.recv() => match triggered {
+                Some(triggered) => triggered,
+                None => break
+            }
+        };
+        let TriggeredActions { category, origin, scheduling } = triggered;
         let category_name;
         let action;
 
@@ -43,7 +127,7 @@ pub async fn redirect_action_in_queue(category_receiver: &mut Receiver<ActionCat
 
         match queue_notifiers.get_mut(&category_name) {
             Some(sender) => {
-                match sender.send(action).await {
+                match sender.send(QueuedAction::new(action, origin, scheduling)) {
                     Ok(_) => debug!("Redirect OK on category {}", category_name),
                     Err(e) => error!("redirect_action_in_queue::redirect_error::{}", e)
                 };
@@ -55,38 +139,194 @@ pub async fn redirect_action_in_queue(category_receiver: &mut Receiver<ActionCat
     }
 }
 
-pub async fn actions_queue(rxi: &mut Receiver<Action>) -> () {
-    let mut action_handler = ActionHandler::default();
-    let actions_to_enqueue = Arc::new(Mutex::new(Vec::<Action>::new()));
-    let actions_to_dequeue = actions_to_enqueue.clone();
+/// Runs the category queues, the ones created on reload included. Ends when no more queues can be added and every queue ended
+pub async fn run_queues(mut added_queues: UnboundedReceiver<Pin<Box<dyn Future<Output = ()>>>>) {
+    let mut queues = FuturesUnordered::new();
+    let mut adding = true;
+    loop {
+        tokio::select! {
+            added = added_queues.recv(), if adding => match added {
+                Some(queue) => queues.push(queue),
+                None => adding = false
+            },
+            Some(()) = queues.next(), if !queues.is_empty() => (),
+            else => break
+        }
+    }
+}
+
+/// Sleeps until the next action is due, a new action arrives, a control is received or another category takes or releases its locks.
+/// An idle queue doesn't wake up, a paused queue with actions waiting checks its pause conditions periodically.
+/// When no more actions can arrive the queue ends once it ran everything and released the keys, dropping the handler releases the rest
+pub async fn actions_queue(category_name: &str, rxi: &mut UnboundedReceiver<QueuedAction>, mut control: broadcast::Receiver<QueueControl>, events: broadcast::Sender<QueueEvent>, mut action_handler: ActionHandler, mut pending: PendingActions, mut locks: CategoryLocks) -> () {
+    let mut running = VecDeque::<Action>::new();
+    let mut paused = false;
+    let (mut receiving, mut controlled, mut closed) = (true, true, false);
 
-    let feeder = async move {
-        while let Some(a) = rxi.recv().await {
-            debug!("Feed action {:?}", a);
-            let mut actions = actions_to_enqueue.lock().unwrap();
-            actions.push(a);
-            drop(actions);
+    loop {
+        let wake_up_at = run_due_actions(category_name, &mut running, &mut pending, &mut locks, &mut action_handler, &events, &mut paused);
+        if !receiving && wake_up_at.is_none() && running.is_empty() && pending.is_empty() {
+            break;
         }
-    };
 
-    let runner = async move {
-        loop {
-            if !action_handler.can_handle() {
-                sleep(Duration::from_millis(100)).await;
-                continue;
-            }
+        tokio::select! {
+            // Controls first, a pause sent together with an action has to stop it
+            biased;
+            queue_control = control.recv(), if controlled => match queue_control {
+                Ok(queue_control) => {
+                    if queue_control.command == QueueCommand::Close && queue_control.applies_to(category_name) {
+                        rxi.close();
+                        closed = true;
+                    }
+                    apply_queue_control(category_name, queue_control, &mut running, &mut pending, &mut action_handler)
+                },
+                Err(RecvError::Lagged(skipped)) => error!("Queue {} missed {} controls", category_name, skipped),
+                Err(RecvError::Closed) => controlled = false
+            },
+            received = rxi.recv(), if receiving => match received {
+                Some(queued) if closed => debug!("Queue {} closed, discarded action {:?}", category_name, queued.action),
+                Some(queued) => feed_action(category_name, queued, &mut running, &mut pending, &mut action_handler, &events),
+                None => receiving = false
+            },
+            _ = sleep_until(wake_up_at.unwrap_or_else(Instant::now)), if wake_up_at.is_some() => (),
+            _ = locks.changed(), if receiving || controlled => (),
+            else => break
+        }
+    }
+}
+
+/// Runs what is due, returns when the queue has to run again or None if only new actions or controls can change something.
+/// Running holds the steps left of the action started, pending the actions that haven't started yet.
+/// Another category running exclusively pauses the queue at its next step, it releases what it holds and steps aside
+fn run_due_actions(category_name: &str, running: &mut VecDeque<Action>, pending: &mut PendingActions, locks: &mut CategoryLocks, action_handler: &mut ActionHandler, events: &broadcast::Sender<QueueEvent>, paused: &mut bool) -> Option<Instant> {
+    let exclusive_reason = locks.pause_reason();
+    if exclusive_reason.is_some() {
+        action_handler.release_for_pause();
+        locks.step_aside();
+    }
+    let pause_reason = action_handler.pause_reason().or(exclusive_reason);
+    if pause_reason.is_some() != *paused {
+        *paused = pause_reason.is_some();
+        notify_queue_event(events, match pause_reason {
+            Some(reason) => QueueEvent::Paused { category: s!(category_name), reason },
+            None => QueueEvent::Resumed { category: s!(category_name) }
+        });
+    }
+
+    if *paused {
+        let waiting = !running.is_empty() || !pending.is_empty();
+        let pause_check = if waiting { Some(Instant::now() + Duration::from_millis(PAUSE_CHECK_INTERVAL_MS)) } else { None };
+        return action_handler.release_due_keys().into_iter().chain(pause_check).min();
+    }
 
-            let mut actions = actions_to_dequeue.lock().unwrap();
-            action_handler.run(actions.deref_mut());
-            drop(actions);
-            sleep(Duration::from_millis(10)).await;
+    if let Some(reason) = action_handler.drop_reason() {
+        if !running.is_empty() || !pending.is_empty() {
+            let dropped = pending.len() + if running.is_empty() { 0 } else { 1 };
+            info!("Queue {} dropped {} actions: {}", category_name, dropped, reason);
+            running.clear();
+            pending.clear();
+            pending.finish_running();
+            locks.release();
+            locks.stop_waiting();
+            action_handler.release_all();
         }
+        return action_handler.release_due_keys();
+    }
+
+    loop {
+        let wake_up_at = action_handler.run(running);
+        if !running.is_empty() {
+            return wake_up_at;
+        }
+        pending.finish_running();
+        locks.release();
+        discard_stale(category_name, pending);
+        if pending.is_empty() {
+            locks.stop_waiting();
+            return wake_up_at;
+        }
+        if !locks.acquire() {
+            return wake_up_at;
+        }
+        running.extend(pending.pop());
+    }
+}
+
+/// Adds the action to the pending ones, a preempting action accepted stops the running one releasing what it holds
+fn feed_action(category_name: &str, queued: QueuedAction, running: &mut VecDeque<Action>, pending: &mut PendingActions, action_handler: &mut ActionHandler, events: &broadcast::Sender<QueueEvent>) {
+    debug!("Feed action {:?}", queued.action);
+    discard_stale(category_name, pending);
+    let preemption = queued.scheduling.preempt.filter(|_| pending.preempts(&queued));
+    let accepted = match pending.push(queued) {
+        Some(overflow) => {
+            let accepted = overflow.policy == OverflowPolicy::DropOldest;
+            notify_overflow(category_name, overflow, events);
+            accepted
+        },
+        None => true
     };
 
-    join!(feeder, runner);
+    if let (true, Some(preemption)) = (accepted, preemption) {
+        running.clear();
+        action_handler.release_all();
+        if let Some(interrupted) = pending.interrupt_running(preemption) {
+            info!("Queue {} interrupted {:?}, {:?}", category_name, interrupted, preemption);
+        }
+    }
+}
+
+fn discard_stale(category_name: &str, pending: &mut PendingActions) {
+    let stale = pending.discard_stale();
+    if stale > 0 {
+        info!("Queue {} discarded {} actions waiting for too long", category_name, stale);
+    }
 }
 
-fn add_uncategorized(custom_categories: Vec<String>) -> Vec<String> {
+fn notify_overflow(category_name: &str, overflow: Overflow, events: &broadcast::Sender<QueueEvent>) {
+    match overflow.policy {
+        OverflowPolicy::DropNewest => info!("Queue {} full, dropped new action {:?} of {}", category_name, overflow.discarded.action, overflow.discarded.origin.user),
+        OverflowPolicy::DropOldest => info!("Queue {} full, dropped oldest action {:?} of {}", category_name, overflow.discarded.action, overflow.discarded.origin.user),
+        OverflowPolicy::Reject => notify_queue_event(events, QueueEvent::Rejected { category: s!(category_name), user: overflow.discarded.origin.user, action: overflow.discarded.action })
+    }
+}
+
+fn apply_queue_control(category_name: &str, queue_control: QueueControl, running: &mut VecDeque<Action>, pending: &mut PendingActions, action_handler: &mut ActionHandler) {
+    if !queue_control.applies_to(category_name) {
+        return;
+    }
+    info!("Queue {} received {:?}", category_name, queue_control.command);
+    match queue_control.command {
+        QueueCommand::ReleaseAll => action_handler.release_all(),
+        QueueCommand::Pause => action_handler.set_paused_by_command(true),
+        QueueCommand::Resume => action_handler.set_paused_by_command(false),
+        QueueCommand::Clear => {
+            info!("Queue {} cleared {} actions", category_name, pending.len());
+            pending.clear();
+        },
+        QueueCommand::Skip => {
+            running.clear();
+            action_handler.release_all();
+        },
+        QueueCommand::Close => {
+            pending.clear();
+            running.clear();
+            action_handler.release_all();
+        }
+    }
+}
+
+fn notify_queue_event(events: &broadcast::Sender<QueueEvent>, event: QueueEvent) {
+    match &event {
+        QueueEvent::Paused { category, reason } => info!("Queue {} paused: {}", category, reason),
+        QueueEvent::Resumed { category } => info!("Queue {} resumed", category),
+        QueueEvent::Rejected { category, user, action } => info!("Queue {} full, rejected action {:?} of {}", category, action, user)
+    }
+    // Nobody listening is fine, the transition is logged anyway
+    let _ = events.send(event);
+}
+
+/// Categories with a queue, the uncategorized one included
+pub fn add_uncategorized(custom_categories: Vec<String>) -> Vec<String> {
     let mut all_categories = custom_categories.clone();
     all_categories = all_categories
         .iter()
@@ -96,3 +336,284 @@ fn add_uncategorized(custom_categories: Vec<String>) -> Vec<String> {
 
     all_categories
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::actions::action::{ActionOrigin, Preemption, Scheduling};
+    use crate::actions::pause::PauseConditions;
+    use crate::actions::queue_policy::queue_policy_birth;
+    use crate::actions::locks::{category_locks_birth, SharedLocks};
+    use crate::system_input::recording::recording_system_input::{RecordingSystemInput, RecordedInput, Timeline};
+    use crate::utils::app_config::CategoryQueueConfig;
+    use tokio::task::yield_now;
+    use tokio::time::{advance, pause, sleep};
+
+    #[tokio::test]
+    async fn waits_and_holds_executed_at_exact_time() {
+        pause();
+        let timeline = Timeline::default();
+        let (sender, queue) = test_queue(&timeline, PendingActions::default());
+
+        queue.run_while(async {
+            sender.send(queued(Action::Sequence(vec![Action::KeyRawDown(38), Action::WaitFor(250), Action::KeyRawUp(38)]))).unwrap();
+            sender.send(queued(Action::Press { keys: vec![32], hold_ms: 40 })).unwrap();
+            sleep(Duration::from_secs(1)).await;
+        }).await;
+
+        assert_timeline(&timeline, vec![
+            (0, RecordedInput::KeyDown(38)),
+            (250, RecordedInput::KeyUp(38)),
+            (250, RecordedInput::KeyDown(32)),
+            (290, RecordedInput::KeyUp(32))
+        ]);
+    }
+
+    #[tokio::test]
+    async fn action_received_while_waiting_runs_after_the_wait() {
+        pause();
+        let timeline = Timeline::default();
+        let (sender, queue) = test_queue(&timeline, PendingActions::default());
+
+        queue.run_while(async {
+            sender.send(queued(Action::Sequence(vec![Action::WaitFor(100), Action::KeyRawDown(38)]))).unwrap();
+            sleep(Duration::from_millis(30)).await;
+            sender.send(queued(Action::KeyRawUp(38))).unwrap();
+            sleep(Duration::from_secs(1)).await;
+        }).await;
+
+        assert_timeline(&timeline, vec![
+            (100, RecordedInput::KeyDown(38)),
+            (100, RecordedInput::KeyUp(38))
+        ]);
+    }
+
+    #[tokio::test]
+    async fn paused_by_command_then_actions_run_after_resume() {
+        pause();
+        let timeline = Timeline::default();
+        let (sender, queue) = test_queue(&timeline, PendingActions::default());
+        let control_notifier = queue.control_notifier.clone();
+
+        queue.run_while(async {
+            control_notifier.send(QueueControl::all(QueueCommand::Pause)).unwrap();
+            sender.send(queued(Action::Press { keys: vec![38], hold_ms: 50 })).unwrap();
+            sleep(Duration::from_secs(1)).await;
+            control_notifier.send(QueueControl::all(QueueCommand::Resume)).unwrap();
+            sleep(Duration::from_millis(100)).await;
+        }).await;
+
+        assert_timeline(&timeline, vec![
+            (1000, RecordedInput::KeyDown(38)),
+            (1050, RecordedInput::KeyUp(38))
+        ]);
+    }
+
+    #[tokio::test]
+    async fn skip_releases_the_running_action_and_clear_discards_the_waiting_ones() {
+        pause();
+        let timeline = Timeline::default();
+        let (sender, queue) = test_queue(&timeline, PendingActions::default());
+        let control_notifier = queue.control_notifier.clone();
+
+        queue.run_while(async {
+            sender.send(queued(Action::Press { keys: vec![37], hold_ms: 1000 })).unwrap();
+            sender.send(queued(Action::Press { keys: vec![38], hold_ms: 1000 })).unwrap();
+            sender.send(queued(Action::Press { keys: vec![39], hold_ms: 50 })).unwrap();
+            sleep(Duration::from_millis(100)).await;
+            control_notifier.send(QueueControl::all(QueueCommand::Skip)).unwrap();
+            sleep(Duration::from_millis(100)).await;
+            control_notifier.send(QueueControl { category: Some(s!("test")), command: QueueCommand::Clear }).unwrap();
+            sleep(Duration::from_secs(2)).await;
+        }).await;
+
+        assert_timeline(&timeline, vec![
+            (0, RecordedInput::KeyDown(37)),
+            (100, RecordedInput::KeyUp(37)),
+            (100, RecordedInput::KeyDown(38)),
+            (1100, RecordedInput::KeyUp(38))
+        ]);
+    }
+
+    #[tokio::test]
+    async fn queue_ends_after_running_everything_once_no_more_actions_can_arrive() {
+        pause();
+        let timeline = Timeline::default();
+        let (sender, queue) = test_queue(&timeline, PendingActions::default());
+        let TestQueue { category, mut receiver, control, events, handler, pending, locks, .. } = queue;
+
+        sender.send(queued(Action::Press { keys: vec![38], hold_ms: 100 })).unwrap();
+        sender.send(queued(Action::Press { keys: vec![40], hold_ms: 50 })).unwrap();
+        drop(sender);
+        actions_queue(category, &mut receiver, control, events, handler, pending, locks).await;
+
+        assert_timeline(&timeline, vec![
+            (0, RecordedInput::KeyDown(38)),
+            (100, RecordedInput::KeyUp(38)),
+            (100, RecordedInput::KeyDown(40)),
+            (150, RecordedInput::KeyUp(40))
+        ]);
+    }
+
+    #[tokio::test]
+    async fn redirect_routes_to_queues_created_and_not_to_retired_ones() {
+        let (category_notifier, mut category_receiver) = tokio::sync::mpsc::channel::<TriggeredActions>(10);
+        let (queue_notifiers, mut queue_receivers) = action_queue_coordinators(vec![s!("movement")]);
+        let (change_notifier, changes) = unbounded_channel::<QueueChange>();
+        let (camera_sender, mut camera_receiver) = unbounded_channel::<QueuedAction>();
+        let triggered = |category: &str| TriggeredActions { category: ActionCategory::WithCategory(s!(category), Action::KeyRawDown(38)), origin: ActionOrigin::default(), scheduling: Scheduling::default() };
+
+        change_notifier.send(QueueChange::Created(s!("camera"), camera_sender)).unwrap();
+        change_notifier.send(QueueChange::Retired(s!("movement"))).unwrap();
+        category_notifier.send(triggered("camera")).await.unwrap();
+        category_notifier.send(triggered("movement")).await.unwrap();
+        drop(category_notifier);
+        redirect_action_in_queue(&mut category_receiver, queue_notifiers, changes).await;
+
+        assert_eq!(camera_receiver.recv().await.map(|queued| queued.action), Some(Action::KeyRawDown(38)));
+        assert!(queue_receivers.get_mut("movement").unwrap().recv().await.is_none());
+    }
+
+    #[tokio::test]
+    async fn full_queue_rejects_and_stale_actions_never_run() {
+        pause();
+        let timeline = Timeline::default();
+        let limits = CategoryQueueConfig { max_length: Some(2), overflow: s!("reject"), ttl_seconds: Some(60), ..Default::default() };
+        let (sender, queue) = test_queue(&timeline, PendingActions::new(queue_policy_birth(&limits).unwrap()));
+        let mut events = queue.events.subscribe();
+
+        queue.run_while(async {
+            sender.send(queued(Action::Press { keys: vec![37], hold_ms: 120_000 })).unwrap();
+            sender.send(queued(Action::KeyRawDown(38))).unwrap();
+            sender.send(queued(Action::KeyRawDown(39))).unwrap();
+            sender.send(queued(Action::KeyRawDown(40))).unwrap();
+            sleep(Duration::from_secs(121)).await;
+            sender.send(queued(Action::KeyRawUp(40))).unwrap();
+            sleep(Duration::from_millis(1)).await;
+        }).await;
+
+        assert_eq!(events.try_recv().unwrap(), QueueEvent::Rejected { category: s!("test"), user: s!("viewer"), action: Action::KeyRawDown(40) });
+        assert_eq!(timeline.entries().iter().map(|(_, input)| input.clone()).collect::<Vec<_>>(), vec![
+            RecordedInput::KeyDown(37),
+            RecordedInput::KeyUp(37),
+            RecordedInput::KeyUp(40)
+        ]);
+    }
+
+    #[tokio::test]
+    async fn preempting_action_interrupts_releasing_keys_then_interrupted_one_resumes() {
+        pause();
+        let timeline = Timeline::default();
+        let (sender, queue) = test_queue(&timeline, PendingActions::default());
+
+        queue.run_while(async {
+            sender.send(queued(Action::Press { keys: vec![38], hold_ms: 1000 })).unwrap();
+            while timeline.entries().is_empty() {
+                yield_now().await;
+            }
+            // Advancing the clock doesn't round up like a sleep, the preempting action arrives at 100ms exactly
+            advance(Duration::from_millis(100)).await;
+            let preempting = Scheduling { priority: 1, preempt: Some(Preemption::Resume) };
+            sender.send(QueuedAction::new(Action::Press { keys: vec![40], hold_ms: 50 }, ActionOrigin::default(), preempting)).unwrap();
+            sleep(Duration::from_secs(2)).await;
+        }).await;
+
+        assert_timeline(&timeline, vec![
+            (0, RecordedInput::KeyDown(38)),
+            (100, RecordedInput::KeyUp(38)),
+            (100, RecordedInput::KeyDown(40)),
+            (150, RecordedInput::KeyUp(40)),
+            (150, RecordedInput::KeyDown(38)),
+            (1150, RecordedInput::KeyUp(38))
+        ]);
+    }
+
+    #[tokio::test]
+    async fn exclusive_category_pauses_other_categories_between_steps() {
+        pause();
+        let timeline = Timeline::default();
+        let shared_locks = SharedLocks::default();
+        let menu_locks = category_locks_birth("menu", &CategoryQueueConfig { exclusive: true, ..Default::default() }, &shared_locks).unwrap();
+        let (menu_sender, menu) = category_test_queue("menu", &timeline, PendingActions::default(), menu_locks);
+        let movement_locks = category_locks_birth("movement", &CategoryQueueConfig::default(), &shared_locks).unwrap();
+        let (movement_sender, movement) = category_test_queue("movement", &timeline, PendingActions::default(), movement_locks);
+
+        tokio::select! {
+            _ = menu.run() => (),
+            _ = movement.run() => (),
+            _ = async {
+                movement_sender.send(queued(Action::Sequence(vec![
+                    Action::KeyRawDown(87), Action::WaitFor(50), Action::KeyRawUp(87), Action::KeyRawDown(65), Action::WaitFor(10), Action::KeyRawUp(65)
+                ]))).unwrap();
+                while timeline.entries().is_empty() {
+                    yield_now().await;
+                }
+                advance(Duration::from_millis(20)).await;
+                menu_sender.send(queued(Action::Press { keys: vec![27], hold_ms: 100 })).unwrap();
+                sleep(Duration::from_secs(1)).await;
+            } => ()
+        }
+
+        // Movement releases W at its next step before the menu presses Escape and doesn't release it again when it resumes
+        assert_timeline(&timeline, vec![
+            (0, RecordedInput::KeyDown(87)),
+            (20, RecordedInput::KeyUp(87)),
+            (20, RecordedInput::KeyDown(27)),
+            (120, RecordedInput::KeyUp(27)),
+            (120, RecordedInput::KeyDown(65)),
+            (130, RecordedInput::KeyUp(65))
+        ]);
+    }
+
+    /// Tokio timers have millisecond granularity and round deadlines up, so an input can come a couple of ms late but never early
+    fn assert_timeline(timeline: &Timeline, expected: Vec<(u64, RecordedInput)>) {
+        let entries = timeline.entries();
+        assert_eq!(entries.iter().map(|(_, input)| input.clone()).collect::<Vec<_>>(), expected.iter().map(|(_, input)| input.clone()).collect::<Vec<_>>());
+        for ((elapsed, _), (expected_ms, _)) in entries.iter().zip(expected.iter()) {
+            let expected_elapsed = Duration::from_millis(*expected_ms);
+            assert!(*elapsed >= expected_elapsed && *elapsed <= expected_elapsed + Duration::from_millis(2), "{:?} instead of {:?}", elapsed, expected_elapsed);
+        }
+    }
+
+    fn queued(action: Action) -> QueuedAction {
+        QueuedAction::new(action, ActionOrigin { user: s!("viewer"), ..Default::default() }, Scheduling::default())
+    }
+
+    struct TestQueue {
+        category: &'static str,
+        receiver: UnboundedReceiver<QueuedAction>,
+        control_notifier: broadcast::Sender<QueueControl>,
+        control: broadcast::Receiver<QueueControl>,
+        events: broadcast::Sender<QueueEvent>,
+        handler: ActionHandler,
+        pending: PendingActions,
+        locks: CategoryLocks
+    }
+
+    impl TestQueue {
+        async fn run(self) {
+            let TestQueue { category, mut receiver, control, events, handler, pending, locks, .. } = self;
+            actions_queue(category, &mut receiver, control, events, handler, pending, locks).await;
+            panic!("Queue stopped before the end of the test");
+        }
+
+        async fn run_while(self, test: impl std::future::Future<Output = ()>) {
+            tokio::select! {
+                _ = self.run() => (),
+                _ = test => ()
+            }
+        }
+    }
+
+    fn test_queue(timeline: &Timeline, pending: PendingActions) -> (UnboundedSender<QueuedAction>, TestQueue) {
+        category_test_queue("test", timeline, pending, category_locks_birth("test", &CategoryQueueConfig::default(), &SharedLocks::default()).unwrap())
+    }
+
+    fn category_test_queue(category: &'static str, timeline: &Timeline, pending: PendingActions, locks: CategoryLocks) -> (UnboundedSender<QueuedAction>, TestQueue) {
+        let (sender, receiver) = unbounded_channel::<QueuedAction>();
+        let (control_notifier, control) = queue_control_channel();
+        let (events, _) = queue_event_channel();
+        let handler = ActionHandler::new(Box::new(RecordingSystemInput::new(timeline.clone())), PauseConditions::default(), None, None);
+        (sender, TestQueue { category, receiver, control_notifier, control, events, handler, pending, locks })
+    }
+}
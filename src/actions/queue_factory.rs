@@ -0,0 +1,71 @@
+use std::future::Future;
+use std::pin::Pin;
+use tokio::sync::broadcast;
+use tokio::sync::mpsc::UnboundedReceiver;
+use crate::actions::action::QueuedAction;
+use crate::actions::handler::ActionHandler;
+use crate::actions::locks::{category_locks_birth, SharedLocks};
+use crate::actions::pause::pause_conditions_birth;
+use crate::actions::queue::{actions_queue, QueueControl, QueueEvent};
+use crate::actions::queue_policy::{queue_policy_birth, PendingActions};
+use crate::actions::window_guard::window_guard_birth;
+use crate::system_input::gamepad_output::SharedGamepad;
+use crate::system_input::system_input_backend::{create_system_input, create_state_system_input, input_backend_birth, InputBackend};
+use crate::utils::app_config::{InputConfig, PauseConfig, TargetWindowConfig, QueueConfig};
+
+/// Configuration of the category queues, reloading it affects the queues created after
+pub struct QueueSettings {
+    pub input: InputConfig,
+    pub pause: PauseConfig,
+    pub target_window: TargetWindowConfig,
+    pub queue: QueueConfig
+}
+
+/// Builds the category queues, they share the gamepad, the locks and the control and event channels
+pub struct QueueFactory {
+    pub settings: QueueSettings,
+    gamepad: Option<SharedGamepad>,
+    locks: SharedLocks,
+    control_notifier: broadcast::Sender<QueueControl>,
+    event_notifier: broadcast::Sender<QueueEvent>
+}
+
+pub fn queue_factory_birth(settings: QueueSettings, gamepad: Option<SharedGamepad>, control_notifier: broadcast::Sender<QueueControl>, event_notifier: broadcast::Sender<QueueEvent>) -> QueueFactory {
+    QueueFactory { settings, gamepad, locks: SharedLocks::default(), control_notifier, event_notifier }
+}
+
+impl QueueFactory {
+    /// Queue of the category running the actions received, the error is a wrong setting of the category
+    pub fn queue(&self, category: &str, mut receiver: UnboundedReceiver<QueuedAction>) -> Result<Pin<Box<dyn Future<Output = ()>>>, String> {
+        let QueueSettings { input, pause, target_window, queue } = &self.settings;
+        let pause_conditions = pause_conditions_birth(pause.category_conditions(category))?;
+        let window_guard = window_guard_birth(target_window.category_target(category))?;
+        let reads_input_state = pause_conditions.reads_input_state();
+        let reads_focused_window = window_guard.is_some();
+        let mut action_handler = ActionHandler::new(create_system_input(input, category)?, pause_conditions, window_guard, self.gamepad.clone());
+        if reads_input_state || reads_focused_window {
+            let backend = input_backend_birth(input.category_backend(category))?;
+            match create_state_system_input(backend)? {
+                Some(state_input) => action_handler = action_handler.read_state_on(state_input),
+                None if backend == InputBackend::Recording => {
+                    if reads_input_state {
+                        warn!("Category {} records its input, its mouse_left_down and recent_input pause conditions never pause it", category);
+                    }
+                    if reads_focused_window {
+                        warn!("Category {} records its input, its target window is never checked", category);
+                    }
+                },
+                None => ()
+            }
+        }
+        let pending = PendingActions::new(queue_policy_birth(queue.category_queue(category))?);
+        let locks = category_locks_birth(category, queue.category_queue(category), &self.locks)?;
+        let control = self.control_notifier.subscribe();
+        let events = self.event_notifier.clone();
+        let category = category.to_string();
+
+        Ok(Box::pin(async move {
+            actions_queue(&category, &mut receiver, control, events, action_handler, pending, locks).await;
+        }))
+    }
+}
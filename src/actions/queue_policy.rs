@@ -0,0 +1,387 @@
+use std::collections::VecDeque;
+use tokio::time::{Duration, Instant};
+use crate::actions::action::{Action, Preemption, QueuedAction};
+use crate::utils::app_config::CategoryQueueConfig;
+use crate::{s};
+
+pub const DEFAULT_MAX_LENGTH: usize = 100;
+const DEFAULT_SUBSCRIBER_WEIGHT: u32 = 2;
+const DEFAULT_BITS_WEIGHT: u32 = 3;
+
+/// What happens to an action arriving in a full queue
+#[derive(Debug)]
+#[derive(Clone, Copy)]
+#[derive(PartialEq)]
+pub enum OverflowPolicy {
+    DropNewest,
+    DropOldest,
+    Reject
+}
+
+/// Order in which the actions of different viewers start
+#[derive(Debug)]
+#[derive(Clone, Copy)]
+#[derive(PartialEq)]
+pub enum Fairness {
+    Fifo,
+    /// Viewers take turns, each turn starts as many actions of the viewer as its weight
+    PerUser { subscriber_weight: u32, bits_weight: u32 }
+}
+
+#[derive(Debug)]
+#[derive(Clone)]
+#[derive(PartialEq)]
+pub struct QueuePolicy {
+    max_length: usize,
+    overflow: OverflowPolicy,
+    ttl: Option<Duration>,
+    fairness: Fairness,
+    max_per_user: Option<usize>
+}
+
+impl Default for QueuePolicy {
+    fn default() -> Self {
+        QueuePolicy { max_length: DEFAULT_MAX_LENGTH, overflow: OverflowPolicy::DropNewest, ttl: None, fairness: Fairness::Fifo, max_per_user: None }
+    }
+}
+
+/// Policy of a category as written in the configuration
+pub fn queue_policy_birth(queue: &CategoryQueueConfig) -> Result<QueuePolicy, String> {
+    let overflow = match queue.overflow.as_str() {
+        "" | "drop_newest" => OverflowPolicy::DropNewest,
+        "drop_oldest" => OverflowPolicy::DropOldest,
+        "reject" => OverflowPolicy::Reject,
+        wrong_policy => return Err(format!("Provided wrong queue overflow policy {}", wrong_policy))
+    };
+    let fairness = match queue.fairness.as_str() {
+        "" | "fifo" => Fairness::Fifo,
+        "round_robin" => Fairness::PerUser { subscriber_weight: 1, bits_weight: 1 },
+        "weighted" => Fairness::PerUser {
+            subscriber_weight: queue.subscriber_weight.unwrap_or(DEFAULT_SUBSCRIBER_WEIGHT),
+            bits_weight: queue.bits_weight.unwrap_or(DEFAULT_BITS_WEIGHT)
+        },
+        wrong_fairness => return Err(format!("Provided wrong queue fairness {}", wrong_fairness))
+    };
+    let max_length = queue.max_length.unwrap_or(DEFAULT_MAX_LENGTH);
+    if max_length == 0 || queue.max_per_user == Some(0) {
+        return Err(s!("Provided queue max_length or max_per_user 0, the queue couldn't take any action"));
+    }
+    Ok(QueuePolicy { max_length, overflow, ttl: queue.ttl_seconds.map(Duration::from_secs), fairness, max_per_user: queue.max_per_user })
+}
+
+/// Action discarded because the queue, or the actions of its viewer, were at the maximum.
+/// The policy is the one applied, drop oldest discards the new action when the viewer has nothing waiting
+#[derive(Debug)]
+#[derive(PartialEq)]
+pub struct Overflow {
+    pub policy: OverflowPolicy,
+    pub discarded: QueuedAction
+}
+
+/// Actions waiting to start, bounded by the policy and discarded when older than its TTL.
+/// Higher priority actions start first, the fairness orders the ones with the same priority
+#[derive(Default)]
+pub struct PendingActions {
+    policy: QueuePolicy,
+    actions: VecDeque<QueuedAction>,
+    /// Viewers in turn order when the fairness is per user, the first one is taking its turn
+    turns: VecDeque<String>,
+    started_in_turn: u32,
+    running: Option<QueuedAction>
+}
+
+impl PendingActions {
+    pub fn new(policy: QueuePolicy) -> Self {
+        PendingActions { policy, ..Default::default() }
+    }
+
+    /// Adds the action, or tells which action has been discarded to respect the maximums
+    pub fn push(&mut self, queued: QueuedAction) -> Option<Overflow> {
+        if let Some(max_per_user) = self.policy.max_per_user {
+            if self.in_flight(&queued.origin.user) >= max_per_user {
+                let user = queued.origin.user.clone();
+                return Some(self.overflow(queued, |waiting| waiting.origin.user == user));
+            }
+        }
+        if self.actions.len() >= self.policy.max_length {
+            return Some(self.overflow(queued, |_| true));
+        }
+
+        self.enqueue(queued);
+        None
+    }
+
+    /// Drop oldest discards the oldest action with the lowest priority, never one with a higher priority than the new action
+    fn overflow(&mut self, queued: QueuedAction, replaceable: impl Fn(&QueuedAction) -> bool) -> Overflow {
+        let candidate = |waiting: &QueuedAction| replaceable(waiting) && waiting.scheduling.priority <= queued.scheduling.priority;
+        let oldest = match self.policy.overflow {
+            OverflowPolicy::DropOldest => self.actions.iter()
+                .filter(|waiting| candidate(waiting))
+                .map(|waiting| waiting.scheduling.priority)
+                .min()
+                .and_then(|lowest| self.actions.iter().position(|waiting| candidate(waiting) && waiting.scheduling.priority == lowest)),
+            _ => None
+        };
+        match (oldest, self.policy.overflow) {
+            (Some(index), _) => {
+                let discarded = self.actions.remove(index).unwrap();
+                self.enqueue(queued);
+                Overflow { policy: OverflowPolicy::DropOldest, discarded }
+            },
+            (None, OverflowPolicy::Reject) => Overflow { policy: OverflowPolicy::Reject, discarded: queued },
+            (None, _) => Overflow { policy: OverflowPolicy::DropNewest, discarded: queued }
+        }
+    }
+
+    fn enqueue(&mut self, queued: QueuedAction) {
+        if let Fairness::PerUser { .. } = self.policy.fairness {
+            if !self.turns.contains(&queued.origin.user) {
+                self.turns.push_back(queued.origin.user.clone());
+            }
+        }
+        self.actions.push_back(queued);
+    }
+
+    /// Actions of the viewer waiting or running
+    fn in_flight(&self, user: &str) -> usize {
+        let running = if self.running.as_ref().map(|running| running.origin.user.as_str()) == Some(user) { 1 } else { 0 };
+        running + self.actions.iter().filter(|waiting| waiting.origin.user == user).count()
+    }
+
+    /// Next action to start, it counts as running until `finish_running`
+    pub fn pop(&mut self) -> Option<Action> {
+        let top_priority = self.actions.iter().map(|waiting| waiting.scheduling.priority).max()?;
+        let queued = match self.policy.fairness {
+            Fairness::Fifo => {
+                let index = self.actions.iter().position(|waiting| waiting.scheduling.priority == top_priority)?;
+                self.actions.remove(index)
+            },
+            Fairness::PerUser { subscriber_weight, bits_weight } => self.pop_in_turn(top_priority, subscriber_weight, bits_weight)
+        }?;
+        let action = queued.action.clone();
+        self.running = Some(queued);
+        Some(action)
+    }
+
+    /// The first viewer in turn order with an action of the top priority takes the turn, the others keep their place
+    fn pop_in_turn(&mut self, top_priority: i32, subscriber_weight: u32, bits_weight: u32) -> Option<QueuedAction> {
+        let actions = &self.actions;
+        let taking_turn = self.turns.front().cloned();
+        self.turns.retain(|user| actions.iter().any(|waiting| &waiting.origin.user == user));
+        if self.turns.front() != taking_turn.as_ref() {
+            self.started_in_turn = 0;
+        }
+        let turn = self.turns.iter()
+            .position(|user| actions.iter().any(|waiting| &waiting.origin.user == user && waiting.scheduling.priority == top_priority))?;
+        if turn > 0 {
+            let user = self.turns.remove(turn)?;
+            self.turns.push_front(user);
+            self.started_in_turn = 0;
+        }
+
+        let user = self.turns.front()?;
+        let index = self.actions.iter().position(|waiting| &waiting.origin.user == user && waiting.scheduling.priority == top_priority)?;
+        let queued = self.actions.remove(index)?;
+        self.started_in_turn += 1;
+
+        let mut weight = 1;
+        if queued.origin.is_subscriber {
+            weight = weight.max(subscriber_weight);
+        }
+        if queued.origin.bits > 0 {
+            weight = weight.max(bits_weight);
+        }
+        if self.started_in_turn >= weight {
+            self.started_in_turn = 0;
+            self.turns.rotate_left(1);
+        }
+        Some(queued)
+    }
+
+    pub fn finish_running(&mut self) {
+        self.running = None;
+    }
+
+    /// The action arrived has to interrupt the running one
+    pub fn preempts(&self, queued: &QueuedAction) -> bool {
+        queued.scheduling.preempt.is_some()
+            && matches!(&self.running, Some(running) if queued.scheduling.priority > running.scheduling.priority)
+    }
+
+    /// Stops the running action, with resume it goes back first in line ignoring the max length. Returns the interrupted action
+    pub fn interrupt_running(&mut self, preemption: Preemption) -> Option<Action> {
+        let interrupted = self.running.take()?;
+        let action = interrupted.action.clone();
+        if preemption == Preemption::Resume {
+            if let Fairness::PerUser { .. } = self.policy.fairness {
+                self.turns.retain(|turn| *turn != interrupted.origin.user);
+                self.turns.push_front(interrupted.origin.user.clone());
+                self.started_in_turn = 0;
+            }
+            self.actions.push_front(interrupted);
+        }
+        Some(action)
+    }
+
+    /// Discards the actions waiting for longer than the TTL, returns how many
+    pub fn discard_stale(&mut self) -> usize {
+        let ttl = match self.policy.ttl {
+            Some(ttl) => ttl,
+            None => return 0
+        };
+        let now = Instant::now();
+        let before = self.actions.len();
+        self.actions.retain(|queued| now.duration_since(queued.queued_at) <= ttl);
+        before - self.actions.len()
+    }
+
+    /// Discards the actions waiting, the running one keeps counting until `finish_running`
+    pub fn clear(&mut self) {
+        self.actions.clear();
+        self.turns.clear();
+        self.started_in_turn = 0;
+    }
+
+    pub fn len(&self) -> usize {
+        self.actions.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.actions.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::actions::action::{ActionOrigin, Scheduling};
+    use crate::{s};
+    use tokio::time::{advance, pause};
+
+    #[test]
+    fn full_queue_drops_newest_by_default() {
+        let mut pending = PendingActions::new(queue_policy_birth(&queue(Some(2), "", None)).unwrap());
+
+        assert_eq!(pending.push(queued(1, "a")), None);
+        assert_eq!(pending.push(queued(2, "a")), None);
+        assert_eq!(pending.push(queued(3, "a")).map(|overflow| (overflow.policy, overflow.discarded.action)), Some((OverflowPolicy::DropNewest, Action::KeyRawDown(3))));
+        assert_eq!(pop_all(&mut pending), vec![1, 2]);
+    }
+
+    #[test]
+    fn full_queue_drops_oldest_or_rejects_newest() {
+        let mut dropping_oldest = PendingActions::new(queue_policy_birth(&queue(Some(1), "drop_oldest", None)).unwrap());
+        dropping_oldest.push(queued(1, "a"));
+
+        assert_eq!(dropping_oldest.push(queued(2, "a")).map(|overflow| (overflow.policy, overflow.discarded.action)), Some((OverflowPolicy::DropOldest, Action::KeyRawDown(1))));
+        assert_eq!(pop_all(&mut dropping_oldest), vec![2]);
+
+        let mut rejecting = PendingActions::new(queue_policy_birth(&queue(Some(1), "reject", None)).unwrap());
+        rejecting.push(queued(1, "a"));
+
+        assert_eq!(rejecting.push(queued(2, "a")).map(|overflow| (overflow.policy, overflow.discarded.action)), Some((OverflowPolicy::Reject, Action::KeyRawDown(2))));
+        assert_eq!(pop_all(&mut rejecting), vec![1]);
+    }
+
+    #[tokio::test]
+    async fn actions_older_than_ttl_discarded() {
+        pause();
+        let mut pending = PendingActions::new(queue_policy_birth(&queue(None, "", Some(120))).unwrap());
+        pending.push(queued(37, "a"));
+        advance(Duration::from_secs(100)).await;
+        pending.push(queued(39, "a"));
+        advance(Duration::from_secs(21)).await;
+
+        assert_eq!(pending.discard_stale(), 1);
+        assert_eq!(pop_all(&mut pending), vec![39]);
+    }
+
+    #[test]
+    fn round_robin_takes_turns_between_viewers() {
+        let mut pending = PendingActions::new(queue_policy_birth(&CategoryQueueConfig { fairness: s!("round_robin"), ..Default::default() }).unwrap());
+        for (key, user) in [(1, "a"), (2, "a"), (3, "a"), (4, "b"), (5, "c"), (6, "b")] {
+            pending.push(queued(key, user));
+        }
+
+        assert_eq!(pop_all(&mut pending), vec![1, 4, 5, 2, 6, 3]);
+    }
+
+    #[test]
+    fn weighted_fairness_gives_subscribers_and_cheers_more_turns() {
+        let mut pending = PendingActions::new(queue_policy_birth(&CategoryQueueConfig { fairness: s!("weighted"), subscriber_weight: Some(2), bits_weight: Some(3), ..Default::default() }).unwrap());
+        for key in 1..=4 {
+            pending.push(QueuedAction::new(Action::KeyRawDown(key), ActionOrigin { user: s!("sub"), is_subscriber: true, bits: 0 }, Scheduling::default()));
+        }
+        pending.push(queued(5, "viewer"));
+        pending.push(queued(6, "viewer"));
+        pending.push(QueuedAction::new(Action::KeyRawDown(7), ActionOrigin { user: s!("cheerer"), is_subscriber: false, bits: 100 }, Scheduling::default()));
+
+        assert_eq!(pop_all(&mut pending), vec![1, 2, 5, 7, 3, 4, 6]);
+    }
+
+    #[test]
+    fn viewer_over_max_per_user_counting_the_running_action() {
+        let mut pending = PendingActions::new(queue_policy_birth(&CategoryQueueConfig { max_per_user: Some(2), overflow: s!("reject"), ..Default::default() }).unwrap());
+        pending.push(queued(1, "a"));
+        pending.push(queued(2, "a"));
+
+        assert_eq!(pending.push(queued(3, "a")).map(|overflow| overflow.policy), Some(OverflowPolicy::Reject));
+        assert_eq!(pending.push(queued(4, "b")), None);
+        assert_eq!(pending.pop(), Some(Action::KeyRawDown(1)));
+        assert_eq!(pending.push(queued(3, "a")).map(|overflow| overflow.policy), Some(OverflowPolicy::Reject));
+        pending.finish_running();
+        assert_eq!(pending.push(queued(3, "a")), None);
+    }
+
+    #[test]
+    fn higher_priority_actions_start_first_then_fairness() {
+        let mut pending = PendingActions::new(queue_policy_birth(&CategoryQueueConfig { fairness: s!("round_robin"), ..Default::default() }).unwrap());
+        pending.push(queued(1, "a"));
+        pending.push(queued(2, "a"));
+        pending.push(prioritized(3, "b", 5));
+        pending.push(queued(4, "c"));
+        pending.push(prioritized(5, "a", 5));
+
+        assert_eq!(pop_all(&mut pending), vec![5, 3, 4, 1, 2]);
+    }
+
+    #[test]
+    fn drop_oldest_spares_higher_priority_actions() {
+        let mut pending = PendingActions::new(queue_policy_birth(&queue(Some(2), "drop_oldest", None)).unwrap());
+        pending.push(prioritized(1, "a", 10));
+        pending.push(queued(2, "a"));
+
+        assert_eq!(pending.push(queued(3, "a")).map(|overflow| overflow.discarded.action), Some(Action::KeyRawDown(2)));
+        assert_eq!(pending.push(queued(4, "a")).map(|overflow| overflow.discarded.action), Some(Action::KeyRawDown(3)));
+        assert_eq!(pending.push(prioritized(5, "a", -1)).map(|overflow| (overflow.policy, overflow.discarded.action)), Some((OverflowPolicy::DropNewest, Action::KeyRawDown(5))));
+        assert_eq!(pop_all(&mut pending), vec![1, 4]);
+    }
+
+    #[test]
+    fn wrong_overflow_policy_then_error() {
+        assert_eq!(queue_policy_birth(&queue(None, "drop_random", None)).err(), Some(s!("Provided wrong queue overflow policy drop_random")));
+    }
+
+    fn queue(max_length: Option<usize>, overflow: &str, ttl_seconds: Option<u64>) -> CategoryQueueConfig {
+        CategoryQueueConfig { max_length, overflow: s!(overflow), ttl_seconds, ..Default::default() }
+    }
+
+    fn queued(key: u16, user: &str) -> QueuedAction {
+        QueuedAction::new(Action::KeyRawDown(key), ActionOrigin { user: s!(user), ..Default::default() }, Scheduling::default())
+    }
+
+    fn prioritized(key: u16, user: &str, priority: i32) -> QueuedAction {
+        QueuedAction::new(Action::KeyRawDown(key), ActionOrigin { user: s!(user), ..Default::default() }, Scheduling { priority, preempt: None })
+    }
+
+    fn pop_all(pending: &mut PendingActions) -> Vec<u16> {
+        let mut keys = Vec::new();
+        while let Some(action) = pending.pop() {
+            pending.finish_running();
+            if let Action::KeyRawDown(key) = action {
+                keys.push(key);
+            }
+        }
+        keys
+    }
+}
@@ -0,0 +1,90 @@
+use crate::system_input::system_input::{SystemInput, FocusedWindow};
+use crate::utils::app_config::WindowTargetConfig;
+
+/// What happens to the actions of a queue while the target window isn't focused
+#[derive(Debug)]
+#[derive(Clone, Copy)]
+#[derive(PartialEq)]
+pub enum WindowPolicy {
+    Hold,
+    Drop
+}
+
+#[derive(Debug)]
+pub struct WindowGuard {
+    title: String,
+    process: String,
+    policy: WindowPolicy
+}
+
+/// Guard of a category, None when no target window is configured
+pub fn window_guard_birth(target: &WindowTargetConfig) -> Result<Option<WindowGuard>, String> {
+    if target.title.is_empty() && target.process.is_empty() {
+        return Ok(None);
+    }
+
+    let policy = match target.policy.as_str() {
+        "" | "hold" => WindowPolicy::Hold,
+        "drop" => WindowPolicy::Drop,
+        wrong_policy => return Err(format!("Provided wrong target window policy {}", wrong_policy))
+    };
+    Ok(Some(WindowGuard { title: target.title.to_lowercase(), process: target.process.to_lowercase(), policy }))
+}
+
+impl WindowGuard {
+    pub fn policy(&self) -> WindowPolicy {
+        self.policy
+    }
+
+    /// Title contains the configured one and process is the configured one, both case insensitive
+    pub fn matches(&self, window: &FocusedWindow) -> bool {
+        (self.title.is_empty() || window.title.to_lowercase().contains(&self.title))
+            && (self.process.is_empty() || window.process.to_lowercase() == self.process)
+    }
+
+    /// Reason to hold or drop the actions, None when the target window is focused or the backend can't know it
+    pub fn check(&self, input_system: &dyn SystemInput) -> Option<String> {
+        match input_system.focused_window() {
+            Some(window) if !self.matches(&window) => Some(format!("target window not focused, focused window is `{}` of `{}`", window.title, window.process)),
+            _ => None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{s};
+
+    #[test]
+    fn target_window_by_title_and_process_then_both_have_to_match() {
+        let guard = window_guard_birth(&target("Minecraft", "javaw.exe", "")).unwrap().unwrap();
+
+        assert_eq!(guard.policy(), WindowPolicy::Hold);
+        assert!(guard.matches(&window("Twitch Plays Minecraft 1.20", "JavaW.exe")));
+        assert!(!guard.matches(&window("Twitch Plays Minecraft 1.20", "obs64.exe")));
+        assert!(!guard.matches(&window("OBS 29.1", "javaw.exe")));
+    }
+
+    #[test]
+    fn target_window_only_by_process_then_any_title() {
+        let guard = window_guard_birth(&target("", "game", "drop")).unwrap().unwrap();
+
+        assert_eq!(guard.policy(), WindowPolicy::Drop);
+        assert!(guard.matches(&window("", "game")));
+        assert!(!guard.matches(&window("game", "obs")));
+    }
+
+    #[test]
+    fn no_target_window_then_no_guard() {
+        assert_eq!(window_guard_birth(&target("", "", "drop")).map(|guard| guard.is_none()), Ok(true));
+    }
+
+    fn target(title: &str, process: &str, policy: &str) -> WindowTargetConfig {
+        WindowTargetConfig { title: s!(title), process: s!(process), policy: s!(policy) }
+    }
+
+    fn window(title: &str, process: &str) -> FocusedWindow {
+        FocusedWindow { title: s!(title), process: s!(process) }
+    }
+}
@@ -0,0 +1,50 @@
+//! Agent for the game PC in two PCs setups, it executes the input sent by the bot running with the "remote" input backend.
+//! Usage: TWITCH_COMMANDS_AGENT_TOKEN=<token> remote_agent <listen address> [--dry-run]
+//! The token is read from the environment, on the command line other users of the PC could see it
+use std::net::TcpListener;
+use simplelog::{SimpleLogger, LevelFilter, Config, CombinedLogger};
+#[macro_use] extern crate log;
+use twitch_commands::system_input::system_input::SystemInput;
+use twitch_commands::system_input::recording::recording_system_input::RecordingSystemInput;
+use twitch_commands::system_input::remote::remote_agent::serve;
+use twitch_commands::system_input::system_input_backend::create_native_system_input;
+
+const TOKEN_VARIABLE: &str = "TWITCH_COMMANDS_AGENT_TOKEN";
+const USAGE: &str = "Usage: TWITCH_COMMANDS_AGENT_TOKEN=<token> remote_agent <listen address> [--dry-run]";
+
+fn main() {
+    if CombinedLogger::init(vec![SimpleLogger::new(LevelFilter::Info, Config::default())]).is_err() {
+        eprintln!("Failed initializing logger for the agent, nothing will be logged.");
+    }
+
+    let args = std::env::args().skip(1).collect::<Vec<String>>();
+    let dry_run = args.iter().any(|arg| arg == "--dry-run");
+    let address = match args.iter().filter(|arg| !arg.starts_with("--")).collect::<Vec<&String>>().as_slice() {
+        [address] => address.to_string(),
+        _ => {
+            eprintln!("{}", USAGE);
+            std::process::exit(2);
+        }
+    };
+    let token = match std::env::var(TOKEN_VARIABLE) {
+        Ok(token) if !token.is_empty() => token,
+        _ => {
+            eprintln!("{} is not set\n\n{}", TOKEN_VARIABLE, USAGE);
+            std::process::exit(2);
+        }
+    };
+
+    let listener = TcpListener::bind(&address).unwrap_or_else(|e| panic!("Can't listen on {}: {}", address, e));
+    let mut input_system: Box<dyn SystemInput> = if dry_run {
+        info!("Dry run, the input received is logged and not sent to the PC");
+        Box::new(RecordingSystemInput::logging())
+    } else {
+        create_native_system_input().unwrap_or_else(|e| {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        })
+    };
+
+    info!("Remote agent listening on {}", address);
+    serve(listener, token, input_system.as_mut());
+}
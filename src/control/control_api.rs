@@ -0,0 +1,96 @@
+use std::io::{BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream};
+use std::thread;
+use tokio::sync::mpsc::UnboundedSender;
+use crate::control::reload::{ReloadRequest, ReloadSource};
+use crate::{s};
+
+/// Local control API, a line protocol: `profile <name>` switches the profile and `reload` reloads the configuration.
+/// Each line is answered by `ok` or `error <reason>`, the switch itself is logged once the configuration is loaded
+pub fn control_api(address: &str, reload_notifier: UnboundedSender<ReloadRequest>) {
+    match TcpListener::bind(address) {
+        Ok(listener) => {
+            info!("Control API listening on {}", address);
+            thread::spawn(move || serve_control_api(listener, reload_notifier));
+        },
+        Err(e) => error!("control_api::bind_error::{}::{}", address, e)
+    }
+}
+
+fn serve_control_api(listener: TcpListener, reload_notifier: UnboundedSender<ReloadRequest>) {
+    for stream in listener.incoming() {
+        match stream {
+            Ok(stream) => {
+                let reload_notifier = reload_notifier.clone();
+                thread::spawn(move || read_client(stream, reload_notifier));
+            },
+            Err(e) => error!("control_api::accept_error::{}", e)
+        }
+    }
+}
+
+fn read_client(stream: TcpStream, reload_notifier: UnboundedSender<ReloadRequest>) {
+    let mut writer = stream;
+    let reader = match writer.try_clone() {
+        Ok(stream) => BufReader::new(stream),
+        Err(e) => {
+            error!("control_api::clone_error::{}", e);
+            return;
+        }
+    };
+    for line in reader.lines() {
+        let answer = match line {
+            Ok(line) => match request_from_line(&line) {
+                Ok(request) => match reload_notifier.send(request) {
+                    Ok(()) => s!("ok"),
+                    Err(_) => s!("error shutting down")
+                },
+                Err(e) => format!("error {}", e)
+            },
+            Err(_) => break
+        };
+        if writeln!(writer, "{}", answer).is_err() {
+            break;
+        }
+    }
+}
+
+fn request_from_line(line: &str) -> Result<ReloadRequest, String> {
+    let words = line.split_whitespace().collect::<Vec<_>>();
+    match words.as_slice() {
+        ["profile", profile] => Ok(ReloadRequest::switch_profile(profile, ReloadSource::Api)),
+        ["reload"] => Ok(ReloadRequest::reload(ReloadSource::Api)),
+        _ => Err(format!("unknown command {}", line.trim()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::sync::mpsc::unbounded_channel;
+
+    #[test]
+    fn commands_then_reload_requested_and_answered() {
+        let (reload_notifier, mut requests) = unbounded_channel();
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        thread::spawn(move || serve_control_api(listener, reload_notifier));
+
+        let mut writer = TcpStream::connect(address).unwrap();
+        let mut reader = BufReader::new(writer.try_clone().unwrap());
+        let mut answer = |line: &str| {
+            writeln!(writer, "{}", line).unwrap();
+            let mut answer = String::new();
+            reader.read_line(&mut answer).unwrap();
+            answer.trim().to_string()
+        };
+
+        assert_eq!(answer("profile racing"), "ok");
+        assert_eq!(answer("reload"), "ok");
+        assert_eq!(answer("jump"), "error unknown command jump");
+
+        assert_eq!(requests.try_recv().ok(), Some(ReloadRequest::switch_profile("racing", ReloadSource::Api)));
+        assert_eq!(requests.try_recv().ok(), Some(ReloadRequest::reload(ReloadSource::Api)));
+        assert!(requests.try_recv().is_err());
+    }
+}
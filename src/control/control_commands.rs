@@ -0,0 +1,183 @@
+use crate::actions::queue::QueueCommand;
+use crate::stream_interface::events::{ChatEvent, ChatMessage};
+use crate::utils::app_config::{ControlConfig, ControlCommandConfig};
+use crate::{s};
+
+/// Who can use a control command, mods include the broadcaster
+#[derive(Debug)]
+#[derive(Clone, Copy)]
+#[derive(PartialEq)]
+pub enum Role {
+    Broadcaster,
+    Mod,
+    Subscriber,
+    Everyone
+}
+
+impl Role {
+    fn allows(&self, message: &ChatMessage) -> bool {
+        match self {
+            Role::Broadcaster => message.is_broadcaster,
+            Role::Mod => message.is_mod,
+            Role::Subscriber => message.is_subscriber,
+            Role::Everyone => true
+        }
+    }
+}
+
+#[derive(Debug)]
+#[derive(Clone)]
+#[derive(PartialEq)]
+pub enum ControlEffect {
+    /// Stops the stream, the queues release what they hold
+    Stop,
+    Queue(QueueCommand),
+    /// Chat events stop or start producing actions, control commands keep working
+    ToggleAccepting,
+    /// Reads the configuration file again
+    Reload,
+    /// Switches to the named profile
+    SwitchProfile(String)
+}
+
+/// Effect of a control command written in chat, for a category or for every category when None
+#[derive(Debug)]
+#[derive(Clone)]
+#[derive(PartialEq)]
+pub struct Control {
+    pub effect: ControlEffect,
+    pub category: Option<String>
+}
+
+#[derive(Debug)]
+pub struct ControlCommand {
+    trigger: String,
+    roles: Vec<Role>,
+    effect: ControlEffect,
+    /// Category affected, when None it can be written after the trigger
+    category: Option<String>
+}
+
+pub struct ControlCommands {
+    commands: Vec<ControlCommand>
+}
+
+/// Command as written in the configuration, without roles only mods can use it
+pub fn control_command_birth(config: &ControlCommandConfig) -> Result<ControlCommand, String> {
+    if config.trigger.trim().is_empty() || config.trigger.contains(char::is_whitespace) {
+        return Err(format!("Provided wrong control command trigger `{}`, it has to be a single word", config.trigger));
+    }
+    let effect = match config.effect.as_str() {
+        "stop" => ControlEffect::Stop,
+        "release" => ControlEffect::Queue(QueueCommand::ReleaseAll),
+        "pause" => ControlEffect::Queue(QueueCommand::Pause),
+        "resume" => ControlEffect::Queue(QueueCommand::Resume),
+        "clear" => ControlEffect::Queue(QueueCommand::Clear),
+        "skip" => ControlEffect::Queue(QueueCommand::Skip),
+        "toggle_accepting" => ControlEffect::ToggleAccepting,
+        "reload" => ControlEffect::Reload,
+        // Without a profile in the configuration it's written after the trigger
+        "switch_profile" => ControlEffect::SwitchProfile(config.profile.clone()),
+        wrong_effect => return Err(format!("Provided wrong control command effect {} for {}", wrong_effect, config.trigger))
+    };
+    let roles = if config.roles.is_empty() {
+        vec![Role::Mod]
+    } else {
+        config.roles.iter().map(|role| match role.as_str() {
+            "broadcaster" => Ok(Role::Broadcaster),
+            "mod" => Ok(Role::Mod),
+            "subscriber" => Ok(Role::Subscriber),
+            "everyone" => Ok(Role::Everyone),
+            wrong_role => Err(format!("Provided wrong control command role {} for {}", wrong_role, config.trigger))
+        }).collect::<Result<Vec<Role>, String>>()?
+    };
+    let category = Some(&config.category).filter(|category| !category.is_empty()).cloned();
+
+    Ok(ControlCommand { trigger: config.trigger.to_lowercase(), roles, effect, category })
+}
+
+pub fn control_commands_birth(config: &ControlConfig) -> Result<ControlCommands, String> {
+    Ok(ControlCommands { commands: config.commands.iter().map(control_command_birth).collect::<Result<_, String>>()? })
+}
+
+impl ControlCommands {
+    /// Control of a chat message starting with a trigger, when the chatter has one of the roles of the command.
+    /// Queue commands can be followed by a category and switch profile by the profile name, the others ignore the rest
+    pub fn control_from_chat(&self, event: &ChatEvent) -> Option<Control> {
+        let message = match event {
+            ChatEvent::Message(message) => message,
+            _ => return None
+        };
+        let mut words = message.content.split_whitespace();
+        let trigger = words.next()?.to_lowercase();
+        let command = self.commands.iter()
+            .find(|command| command.trigger == trigger && command.roles.iter().any(|role| role.allows(message)))?;
+
+        match &command.effect {
+            ControlEffect::Queue(_) => {
+                let category = command.category.clone().or_else(|| words.next().map(|category| s!(category)));
+                Some(Control { effect: command.effect.clone(), category })
+            },
+            ControlEffect::SwitchProfile(profile) if profile.is_empty() => Some(Control { effect: ControlEffect::SwitchProfile(s!(words.next()?)), category: None }),
+            effect => Some(Control { effect: effect.clone(), category: None })
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn default_commands_for_mods_with_optional_category() {
+        let commands = control_commands_birth(&ControlConfig::default()).unwrap();
+
+        assert_eq!(commands.control_from_chat(&message("!Pause camera", true, false)), Some(Control { effect: ControlEffect::Queue(QueueCommand::Pause), category: Some(s!("camera")) }));
+        assert_eq!(commands.control_from_chat(&message("!skip", true, false)), Some(Control { effect: ControlEffect::Queue(QueueCommand::Skip), category: None }));
+        assert_eq!(commands.control_from_chat(&message("!accepting", true, false)), Some(Control { effect: ControlEffect::ToggleAccepting, category: None }));
+        assert_eq!(commands.control_from_chat(&message("!STOP now", true, false)), Some(Control { effect: ControlEffect::Stop, category: None }));
+        assert_eq!(commands.control_from_chat(&message("!pause", false, true)), None);
+        assert_eq!(commands.control_from_chat(&message("pause", true, false)), None);
+    }
+
+    #[test]
+    fn configured_command_with_roles_and_fixed_category() {
+        let commands = control_commands_birth(&ControlConfig { commands: vec![
+            ControlCommandConfig { trigger: s!("!calm"), effect: s!("clear"), roles: vec![s!("subscriber"), s!("broadcaster")], category: s!("camera"), ..command_config() }
+        ] }).unwrap();
+
+        assert_eq!(commands.control_from_chat(&message("!calm movement", false, true)), Some(Control { effect: ControlEffect::Queue(QueueCommand::Clear), category: Some(s!("camera")) }));
+        assert_eq!(commands.control_from_chat(&message("!calm", true, false)), None);
+        assert_eq!(commands.control_from_chat(&message("!pause", true, false)), None);
+    }
+
+    #[test]
+    fn switch_profile_with_configured_or_written_profile() {
+        let commands = control_commands_birth(&ControlConfig { commands: vec![
+            ControlCommandConfig { trigger: s!("!profile"), effect: s!("switch_profile"), ..command_config() },
+            ControlCommandConfig { trigger: s!("!racing"), effect: s!("switch_profile"), profile: s!("racing"), ..command_config() },
+            ControlCommandConfig { trigger: s!("!reload"), effect: s!("reload"), roles: vec![s!("broadcaster")], ..command_config() }
+        ] }).unwrap();
+
+        assert_eq!(commands.control_from_chat(&message("!profile menu", true, false)), Some(Control { effect: ControlEffect::SwitchProfile(s!("menu")), category: None }));
+        assert_eq!(commands.control_from_chat(&message("!profile", true, false)), None);
+        assert_eq!(commands.control_from_chat(&message("!racing menu", true, false)), Some(Control { effect: ControlEffect::SwitchProfile(s!("racing")), category: None }));
+        assert_eq!(commands.control_from_chat(&message("!reload", true, false)), None);
+    }
+
+    #[test]
+    fn wrong_effect_then_error() {
+        assert_eq!(
+            control_command_birth(&ControlCommandConfig { trigger: s!("!boom"), effect: s!("explode"), ..command_config() }).err(),
+            Some(s!("Provided wrong control command effect explode for !boom"))
+        );
+    }
+
+    fn command_config() -> ControlCommandConfig {
+        ControlCommandConfig { trigger: s!(""), effect: s!(""), roles: vec![], category: s!(""), profile: s!("") }
+    }
+
+    fn message(content: &str, is_mod: bool, is_subscriber: bool) -> ChatEvent {
+        ChatEvent::Message(ChatMessage { name: s!("chatter"), content: s!(content), is_mod, is_broadcaster: false, is_subscriber })
+    }
+}
@@ -0,0 +1,5 @@
+pub mod control_commands;
+pub mod shutdown;
+pub mod reload;
+pub mod profiles;
+pub mod control_api;
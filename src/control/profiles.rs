@@ -0,0 +1,93 @@
+use std::collections::HashMap;
+use tokio::sync::mpsc::UnboundedSender;
+use tokio::time::{interval, Duration};
+use crate::control::reload::{ReloadRequest, ReloadSource};
+use crate::stream_interface::twitch::stream_category::stream_category;
+use crate::stream_interface::twitch::twitch_interface::TwitchConnectOptions;
+use crate::utils::app_config::{Mapping, ProfileConfig};
+
+const DEFAULT_CHECK_INTERVAL_SECONDS: u64 = 60;
+
+/// Mapping of a profile: its own mappings first, then the common ones. The empty profile is the common mapping only
+pub fn profile_mapping(common: &Mapping, profiles: &HashMap<String, Mapping>, profile: &str) -> Result<Mapping, String> {
+    if profile.is_empty() {
+        return Ok(common.clone());
+    }
+    profiles.get(profile)
+        .map(|own| Mapping { config: own.config.iter().chain(common.config.iter()).cloned().collect() })
+        .ok_or_else(|| format!("Provided unknown profile {}", profile))
+}
+
+/// Profile configured for a stream category, the game names are compared ignoring the case
+pub fn profile_for_category<'a>(stream_categories: &'a HashMap<String, String>, category: &str) -> Option<&'a String> {
+    stream_categories.iter()
+        .find(|(game, _)| game.to_lowercase() == category.to_lowercase())
+        .map(|(_, profile)| profile)
+}
+
+/// Asks to switch the profile when the category of the stream changes to a configured one, checking it periodically
+pub async fn switch_profile_on_stream_category(options: TwitchConnectOptions, config: &ProfileConfig, reload_notifier: UnboundedSender<ReloadRequest>) {
+    let mut last_category: Option<String> = None;
+    let mut checks = interval(Duration::from_secs(config.check_interval_seconds.unwrap_or(DEFAULT_CHECK_INTERVAL_SECONDS)));
+    loop {
+        checks.tick().await;
+        let helix_base_url = config.helix_base_url.clone();
+        let stream_options = options.clone();
+        let category = match tokio::task::spawn_blocking(move || stream_category(&helix_base_url, &stream_options)).await {
+            Ok(Ok(category)) => category,
+            Ok(Err(e)) => {
+                warn!("switch_profile_on_stream_category::{}", e);
+                continue;
+            },
+            Err(e) => {
+                error!("switch_profile_on_stream_category::{}", e);
+                continue;
+            }
+        };
+        if category == last_category {
+            continue;
+        }
+        if let Some(profile) = category.as_deref().and_then(|category| profile_for_category(&config.stream_categories, category)) {
+            info!("Stream category changed to {}", category.as_deref().unwrap_or_default());
+            if reload_notifier.send(ReloadRequest::switch_profile(profile, ReloadSource::StreamCategory)).is_err() {
+                return;
+            }
+        }
+        last_category = category;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::app_config::MappingConfig;
+    use crate::{s};
+
+    #[test]
+    fn profile_mappings_come_before_the_common_ones() {
+        let common = mapping(&["!jump"]);
+        let profiles = vec![(s!("racing"), mapping(&["!boost"]))].into_iter().collect();
+
+        let ids = |mapping: Mapping| mapping.config.into_iter().map(|config| config.id).collect::<Vec<_>>();
+        assert_eq!(ids(profile_mapping(&common, &profiles, "racing").unwrap()), vec![s!("!boost"), s!("!jump")]);
+        assert_eq!(ids(profile_mapping(&common, &profiles, "").unwrap()), vec![s!("!jump")]);
+        assert_eq!(profile_mapping(&common, &profiles, "chess").err(), Some(s!("Provided unknown profile chess")));
+    }
+
+    #[test]
+    fn stream_category_matches_ignoring_the_case() {
+        let stream_categories = vec![(s!("Rocket League"), s!("racing"))].into_iter().collect();
+
+        assert_eq!(profile_for_category(&stream_categories, "rocket league"), Some(&s!("racing")));
+        assert_eq!(profile_for_category(&stream_categories, "Just Chatting"), None);
+    }
+
+    fn mapping(ids: &[&str]) -> Mapping {
+        Mapping {
+            config: ids.iter().map(|id| MappingConfig {
+                source: s!("message"), id: s!(id), actions: vec![s!("kd32")], name: s!(""), category: s!(""), comparison: s!(""),
+                limit: None, text: None, priority: 0, preempt: s!("")
+            }).collect()
+        }
+    }
+}
@@ -0,0 +1,204 @@
+use std::convert::TryFrom;
+use std::fs;
+use std::time::SystemTime;
+use tokio::sync::mpsc::UnboundedSender;
+use tokio::time::{interval, Duration};
+use crate::actions::queue::add_uncategorized;
+use crate::actions::queue_factory::QueueSettings;
+use crate::control::profiles::profile_mapping;
+use crate::event_to_action::configurable_event_to_action::configurable_event_to_action::Configuration;
+use crate::utils::app_config::AppConfig;
+use crate::utils::config_validation::validated_app_config;
+
+const DEFAULT_WATCH_INTERVAL_MS: u64 = 1000;
+
+/// What asked to reload the configuration
+#[derive(Debug)]
+#[derive(Clone, Copy)]
+#[derive(PartialEq)]
+pub enum ReloadSource {
+    Command,
+    Signal,
+    FileChanged,
+    Api,
+    StreamCategory
+}
+
+/// Reload of the configuration, switching to another profile or keeping the active one
+#[derive(Debug)]
+#[derive(PartialEq)]
+pub struct ReloadRequest {
+    pub source: ReloadSource,
+    pub profile: Option<String>
+}
+
+impl ReloadRequest {
+    pub fn reload(source: ReloadSource) -> Self {
+        ReloadRequest { source, profile: None }
+    }
+
+    pub fn switch_profile(profile: &str, source: ReloadSource) -> Self {
+        ReloadRequest { source, profile: Some(profile.to_string()) }
+    }
+}
+
+/// Configuration read again, valid for every category queue
+pub struct Reload {
+    pub configuration: Configuration,
+    /// Categories with a queue, the uncategorized one included
+    pub categories: Vec<String>,
+    pub settings: QueueSettings
+}
+
+/// Reads and validates the configuration file with the mapping of the profile. A wrong configuration is the error,
+/// with every problem found, and the current configuration stays
+pub fn load_reload(filename: &str, profile: &str) -> Result<Reload, String> {
+    let AppConfig { mapping, profiles, input, pause, target_window, queue, .. } = validated_app_config(filename)?;
+    let configuration = Configuration::try_from(profile_mapping(&mapping, &profiles, profile)?)?;
+    let categories = add_uncategorized(configuration.categories());
+    Ok(Reload { configuration, categories, settings: QueueSettings { input, pause, target_window, queue } })
+}
+
+/// Asks to reload when the modification time of the file changes, checking it periodically
+pub async fn reload_on_file_change(filename: &str, interval_ms: Option<u64>, reload_notifier: UnboundedSender<ReloadRequest>) {
+    let mut file_changes = FileChanges::new(filename);
+    let mut checks = interval(Duration::from_millis(interval_ms.unwrap_or(DEFAULT_WATCH_INTERVAL_MS)));
+    loop {
+        checks.tick().await;
+        if file_changes.changed() && reload_notifier.send(ReloadRequest::reload(ReloadSource::FileChanged)).is_err() {
+            return;
+        }
+    }
+}
+
+/// Modification time of the file at the last check, a missing file has none
+struct FileChanges {
+    filename: String,
+    last_modified: Option<SystemTime>
+}
+
+impl FileChanges {
+    fn new(filename: &str) -> Self {
+        FileChanges { filename: filename.to_string(), last_modified: modified(filename) }
+    }
+
+    /// True when the modification time is not the one of the last check
+    fn changed(&mut self) -> bool {
+        let modified = modified(&self.filename);
+        let changed = modified != self.last_modified;
+        self.last_modified = modified;
+        changed
+    }
+}
+
+fn modified(filename: &str) -> Option<SystemTime> {
+    fs::metadata(filename).and_then(|metadata| metadata.modified()).ok()
+}
+
+/// Asks to reload on SIGHUP, Unix only. It never ends, not even when the signal can't be listened to
+#[cfg(unix)]
+pub async fn reload_on_signal(reload_notifier: UnboundedSender<ReloadRequest>) {
+    use tokio::signal::unix::{signal, SignalKind};
+
+    match signal(SignalKind::hangup()) {
+        Ok(mut hangups) => {
+            while hangups.recv().await.is_some() {
+                if reload_notifier.send(ReloadRequest::reload(ReloadSource::Signal)).is_err() {
+                    break;
+                }
+            }
+        },
+        Err(e) => error!("reload_on_signal::sighup_error::{}", e)
+    }
+    futures::future::pending::<()>().await
+}
+
+#[cfg(not(unix))]
+pub async fn reload_on_signal(_reload_notifier: UnboundedSender<ReloadRequest>) {
+    futures::future::pending::<()>().await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{s};
+    use crate::utils::test_config::{ConfigFile, VALID_CONFIG};
+    use tokio::sync::mpsc::unbounded_channel;
+    use tokio::task::yield_now;
+    use tokio::time::timeout;
+
+    #[test]
+    fn valid_file_then_mapping_and_categories() {
+        let config_file = ConfigFile::new("reload-valid", VALID_CONFIG);
+
+        let reload = load_reload(&config_file.filename(), "").unwrap();
+
+        let mut categories = reload.categories.clone();
+        categories.sort();
+        assert_eq!(categories, vec![s!("_uncategorized"), s!("movement")]);
+        assert_eq!(reload.configuration.message_options.len(), 1);
+    }
+
+    #[test]
+    fn profile_then_its_mappings_and_categories_added() {
+        let config_file = ConfigFile::new("reload-profile", &format!("{}{}", VALID_CONFIG, r#"
+        [profiles.racing]
+            [[profiles.racing.config]]
+            source = "message"
+            id = "!boost"
+            actions = ["kd16"]
+            category = "driving"
+        "#));
+
+        let reload = load_reload(&config_file.filename(), "racing").unwrap();
+
+        let mut categories = reload.categories.clone();
+        categories.sort();
+        assert_eq!(categories, vec![s!("_uncategorized"), s!("driving"), s!("movement")]);
+        assert_eq!(reload.configuration.message_options.len(), 2);
+        assert_eq!(load_reload(&config_file.filename(), "chess").err(), Some(s!("Provided unknown profile chess")));
+    }
+
+    #[test]
+    fn wrong_file_then_error_telling_where() {
+        let syntax = ConfigFile::new("reload-syntax", &VALID_CONFIG.replace("actions = [\"kd32\"]", "actions = [\"kd32\""));
+        let action = ConfigFile::new("reload-action", &VALID_CONFIG.replace("kd32", "zz32"));
+        let queue = ConfigFile::new("reload-queue", &format!("{}\n[queue]\noverflow = \"explode\"", VALID_CONFIG));
+
+        assert!(load_reload(&syntax.filename(), "").err().unwrap().contains("line "));
+        assert!(load_reload(&action.filename(), "").err().unwrap().contains("zz32"));
+        assert!(load_reload(&queue.filename(), "").err().unwrap().contains("explode"));
+    }
+
+    #[test]
+    fn modification_time_changed_then_change_detected_once() {
+        let config_file = ConfigFile::new("reload-changes", VALID_CONFIG);
+        let mut file_changes = FileChanges::new(&config_file.filename());
+
+        assert!(!file_changes.changed());
+        modify(&config_file, SystemTime::now() + Duration::from_secs(10));
+        assert!(file_changes.changed());
+        assert!(!file_changes.changed());
+    }
+
+    #[tokio::test]
+    async fn file_changed_then_reload_asked() {
+        let config_file = ConfigFile::new("reload-watched", VALID_CONFIG);
+        let (reload_notifier, mut reloads) = unbounded_channel();
+        let watched = config_file.filename();
+        let watching = tokio::spawn(async move { reload_on_file_change(&watched, Some(10), reload_notifier).await });
+
+        // On the test runtime the watcher reads the modification time before the test goes on
+        yield_now().await;
+        modify(&config_file, SystemTime::now() + Duration::from_secs(10));
+
+        assert_eq!(timeout(Duration::from_secs(5), reloads.recv()).await, Ok(Some(ReloadRequest::reload(ReloadSource::FileChanged))));
+        watching.abort();
+    }
+
+    /// Modification time set explicitly, a write alone can keep it on coarse file systems
+    fn modify(config_file: &ConfigFile, modified: SystemTime) {
+        config_file.write(&VALID_CONFIG.replace("kd32", "kd38"));
+        fs::File::options().write(true).open(config_file.filename()).unwrap().set_modified(modified).unwrap();
+    }
+}
@@ -0,0 +1,169 @@
+use std::sync::{Arc, Mutex};
+use tokio::signal;
+use tokio::sync::watch;
+use tokio::time::Duration;
+use crate::utils::app_config::ShutdownConfig;
+
+const DEFAULT_TIMEOUT_SECONDS: u64 = 10;
+
+#[derive(Debug)]
+#[derive(Clone, Copy)]
+#[derive(PartialEq)]
+pub enum ShutdownReason {
+    StopCommand,
+    Interrupted,
+    Terminated,
+    /// The stream ended without being stopped, e.g. the connection was lost
+    StreamEnded,
+    /// Every recorded chat event was replayed
+    ReplayEnded
+}
+
+impl ShutdownReason {
+    /// Exit code of the process, the signals follow the shell convention of 128 + signal number
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            ShutdownReason::StopCommand | ShutdownReason::ReplayEnded => 0,
+            ShutdownReason::Interrupted => 130,
+            ShutdownReason::Terminated => 143,
+            ShutdownReason::StreamEnded => 1
+        }
+    }
+}
+
+#[derive(Debug)]
+#[derive(Clone, Copy)]
+#[derive(PartialEq)]
+pub enum ShutdownMode {
+    /// The queues run the actions already received
+    Drain,
+    /// The queues discard the waiting actions and stop the running ones
+    Clear
+}
+
+#[derive(Debug)]
+pub struct ShutdownPolicy {
+    pub mode: ShutdownMode,
+    /// Time the queues have to end, then what they hold is released and the process exits anyway
+    pub timeout: Duration
+}
+
+pub fn shutdown_policy_birth(config: &ShutdownConfig) -> Result<ShutdownPolicy, String> {
+    let mode = match config.mode.as_str() {
+        "" | "clear" => ShutdownMode::Clear,
+        "drain" => ShutdownMode::Drain,
+        wrong_mode => return Err(format!("Provided wrong shutdown mode {}", wrong_mode))
+    };
+    Ok(ShutdownPolicy { mode, timeout: Duration::from_secs(config.timeout_seconds.unwrap_or(DEFAULT_TIMEOUT_SECONDS)) })
+}
+
+/// Shutdown coordinator, the first reason triggering it wins and every listener is notified
+#[derive(Clone)]
+pub struct Shutdown {
+    reason: Arc<Mutex<Option<ShutdownReason>>>,
+    notifier: Arc<watch::Sender<Option<ShutdownReason>>>,
+    listener: watch::Receiver<Option<ShutdownReason>>
+}
+
+impl Default for Shutdown {
+    fn default() -> Self {
+        let (notifier, listener) = watch::channel(None);
+        Shutdown { reason: Arc::new(Mutex::new(None)), notifier: Arc::new(notifier), listener }
+    }
+}
+
+impl Shutdown {
+    /// False when the shutdown was already triggered
+    pub fn trigger(&self, reason: ShutdownReason) -> bool {
+        let mut triggered = self.reason.lock().unwrap();
+        if triggered.is_some() {
+            return false;
+        }
+        *triggered = Some(reason);
+        info!("Shutdown triggered: {:?}", reason);
+        // The shutdown holds a listener, send can't fail
+        let _ = self.notifier.send(Some(reason));
+        true
+    }
+
+    pub fn reason(&self) -> Option<ShutdownReason> {
+        *self.reason.lock().unwrap()
+    }
+
+    /// Resolves with the reason once the shutdown is triggered
+    pub async fn triggered(&self) -> ShutdownReason {
+        let mut listener = self.listener.clone();
+        loop {
+            if let Some(reason) = *listener.borrow() {
+                return reason;
+            }
+            if listener.changed().await.is_err() {
+                // Can't happen while this shutdown holds the notifier, never resolve instead of spinning
+                futures::future::pending::<()>().await;
+            }
+        }
+    }
+}
+
+/// Triggers the shutdown on Ctrl-C and, on Unix, on SIGTERM
+pub async fn shutdown_on_signals(shutdown: Shutdown) {
+    let reason = tokio::select! {
+        Ok(()) = signal::ctrl_c() => ShutdownReason::Interrupted,
+        _ = terminate_signal() => ShutdownReason::Terminated
+    };
+    shutdown.trigger(reason);
+}
+
+#[cfg(unix)]
+async fn terminate_signal() {
+    match signal::unix::signal(signal::unix::SignalKind::terminate()) {
+        Ok(mut terminate) => {
+            terminate.recv().await;
+        },
+        Err(e) => {
+            error!("shutdown_on_signals::sigterm_error::{}", e);
+            futures::future::pending::<()>().await;
+        }
+    }
+}
+
+#[cfg(not(unix))]
+async fn terminate_signal() {
+    futures::future::pending::<()>().await;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{s};
+
+    #[tokio::test]
+    async fn first_reason_wins_and_listeners_are_notified() {
+        let shutdown = Shutdown::default();
+        let listener = shutdown.clone();
+        let waiting = tokio::spawn(async move { listener.triggered().await });
+
+        assert!(shutdown.trigger(ShutdownReason::StopCommand));
+        assert!(!shutdown.trigger(ShutdownReason::Interrupted));
+
+        assert_eq!(waiting.await.unwrap(), ShutdownReason::StopCommand);
+        assert_eq!(shutdown.reason(), Some(ShutdownReason::StopCommand));
+        assert_eq!(shutdown.triggered().await, ShutdownReason::StopCommand);
+    }
+
+    #[test]
+    fn policy_clears_by_default_with_timeout() {
+        let policy = shutdown_policy_birth(&ShutdownConfig::default()).unwrap();
+        assert_eq!(policy.mode, ShutdownMode::Clear);
+        assert_eq!(policy.timeout, Duration::from_secs(DEFAULT_TIMEOUT_SECONDS));
+
+        let policy = shutdown_policy_birth(&ShutdownConfig { mode: s!("drain"), timeout_seconds: Some(30) }).unwrap();
+        assert_eq!(policy.mode, ShutdownMode::Drain);
+        assert_eq!(policy.timeout, Duration::from_secs(30));
+    }
+
+    #[test]
+    fn wrong_mode_then_error() {
+        assert_eq!(shutdown_policy_birth(&ShutdownConfig { mode: s!("wait"), timeout_seconds: None }).err(), Some(s!("Provided wrong shutdown mode wait")));
+    }
+}
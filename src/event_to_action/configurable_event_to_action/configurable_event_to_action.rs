@@ -1,14 +1,28 @@
 use std::collections::HashSet;
+use std::sync::mpsc::{channel, Receiver, Sender};
 use crate::event_to_action::event_to_action::{EventToAction};
 use crate::stream_interface::events::{ChatEvent};
 use crate::utils::run_on_stream::StreamItemReceiver;
-use crate::actions::action::{Action, ActionCategory};
-use crate::utils::app_config::{Mapping, MappingConfig};
+use crate::actions::action::{Action, ActionCategory, ActionOrigin, Preemption, Scheduling, TriggeredActions};
+use crate::actions::keymap::key_code;
+use crate::actions::movement::{Easing, MouseTarget};
+use crate::utils::app_config::{Mapping, MappingConfig, TextConfig};
+use crate::system_input::system_input::MouseButton;
+use crate::system_input::gamepad_output::{GamepadButton, GamepadStick, GamepadTrigger};
 use std::num::ParseIntError;
+use std::str::FromStr;
+use std::convert::TryFrom;
 use derivative::{Derivative};
+use crate::{s};
+
+const DEFAULT_PRESS_HOLD_MS: u64 = 50;
+const DEFAULT_MOVEMENT_TICK_RATE: u32 = 60;
+const TEXT_ARGUMENTS_PLACEHOLDER: &str = "{args}";
 
 pub struct ConfigurableEventToAction {
-    configuration: Configuration
+    configuration: Configuration,
+    /// Configurations reloaded, the last one replaces the current before the next event
+    updates: Option<Receiver<Configuration>>
 }
 
 pub struct Configuration {
@@ -54,7 +68,51 @@ pub struct ConfigOption {
     pub id: String,
     #[derivative(Debug="ignore")]
     pub actions: ActionCategory,
-    pub times_limit: Option<u16>
+    pub times_limit: Option<u16>,
+    /// Present when the message only has to start with the id, the rest of the message is text for the actions
+    pub arguments: Option<TextSanitizer>,
+    pub scheduling: Scheduling
+}
+
+impl ConfigOption {
+    fn matches(&self, content: &str) -> bool {
+        match self.arguments {
+            None => self.id == content,
+            Some(_) => content == self.id || content.starts_with(&format!("{} ", self.id))
+        }
+    }
+}
+
+#[derive(Debug)]
+#[derive(Default)]
+pub struct TextSanitizer {
+    pub allowed_characters: String,
+    pub max_length: Option<usize>,
+    pub blocked_words: Vec<String>
+}
+
+impl TextSanitizer {
+    /// Text safe to be typed, None if there is nothing left to type or it contains a blocked word
+    fn sanitize(&self, text: &str) -> Option<String> {
+        let allowed_text = text.trim().chars()
+            .filter(|character| !character.is_control())
+            .filter(|character| self.allowed_characters.is_empty() || self.allowed_characters.contains(*character))
+            .take(self.max_length.unwrap_or(usize::MAX))
+            .collect::<String>();
+
+        let lowercase_text = allowed_text.to_lowercase();
+        if allowed_text.is_empty() || self.blocked_words.iter().any(|word| lowercase_text.contains(&word.to_lowercase())) {
+            None
+        } else {
+            Some(allowed_text)
+        }
+    }
+}
+
+impl From<TextConfig> for TextSanitizer {
+    fn from(config: TextConfig) -> Self {
+        TextSanitizer { allowed_characters: config.allowed_characters, max_length: config.max_length, blocked_words: config.blocked_words }
+    }
 }
 
 impl ConfigOptionWithActions for ConfigOption {
@@ -80,7 +138,8 @@ pub struct ConfigActionOption {
     #[derivative(Debug="ignore")]
     pub comparison: Box<dyn Fn(String) -> bool>,
     pub action_name: String,
-    pub times_limit: Option<u16>
+    pub times_limit: Option<u16>,
+    pub scheduling: Scheduling
 }
 
 impl ConfigOptionWithActions for ConfigActionOption {
@@ -97,54 +156,72 @@ impl ConfigOptionWithActions for ConfigActionOption {
     }
 }
 
-impl From<Mapping> for Configuration {
-    fn from(mapping: Mapping) -> Self {
-        Configuration {
+impl TryFrom<Mapping> for Configuration {
+    type Error = String;
+
+    fn try_from(mapping: Mapping) -> Result<Self, String> {
+        Ok(Configuration {
             message_options: mapping.config.iter()
                 .filter(|c| c.source == "message")
                 .map(|message_action| into_option(message_action))
-                .collect::<Vec<ConfigOption>>(),
+                .collect::<Result<Vec<ConfigOption>, String>>()?,
 
             action_options: mapping.config.iter()
                 .filter(|c| c.source == "action")
                 .map(|message_action| into_action_option(message_action))
-                .collect::<Vec<ConfigActionOption>>()
-        }
+                .collect::<Result<Vec<ConfigActionOption>, String>>()?
+        })
     }
 }
 
-fn into_option(mapping: &MappingConfig) -> ConfigOption {
-    ConfigOption {
+fn into_option(mapping: &MappingConfig) -> Result<ConfigOption, String> {
+    Ok(ConfigOption {
         id: mapping.id.clone(),
-        actions: condense_actions(mapping.actions.clone(), mapping.category.clone()),
-        times_limit: mapping.limit
-    }
+        actions: condense_actions(mapping.actions.clone(), mapping.category.clone())?,
+        times_limit: mapping.limit,
+        arguments: match mapping.comparison.as_str() {
+            "prefix" => Some(mapping.text.clone().map(TextSanitizer::from).unwrap_or_default()),
+            _ => None
+        },
+        scheduling: scheduling_birth(mapping)?
+    })
 }
 
-fn into_action_option(mapping: &MappingConfig) -> ConfigActionOption {
-    ConfigActionOption {
+fn into_action_option(mapping: &MappingConfig) -> Result<ConfigActionOption, String> {
+    Ok(ConfigActionOption {
         id: mapping.id.clone(),
-        actions: condense_actions(mapping.actions.clone(), mapping.category.clone()),
-        comparison: into_comparison_fn(mapping.comparison.clone(), mapping.id.clone()),
+        actions: condense_actions(mapping.actions.clone(), mapping.category.clone())?,
+        comparison: into_comparison_fn(mapping.comparison.clone(), mapping.id.clone())?,
         action_name: mapping.name.clone(),
-        times_limit: mapping.limit
-    }
+        times_limit: mapping.limit,
+        scheduling: scheduling_birth(mapping)?
+    })
+}
+
+pub fn scheduling_birth(mapping: &MappingConfig) -> Result<Scheduling, String> {
+    let preempt = match mapping.preempt.as_str() {
+        "" => None,
+        "resume" => Some(Preemption::Resume),
+        "drop" => Some(Preemption::Drop),
+        wrong_preempt => return Err(format!("Provided wrong preempt {} for mapping {}", wrong_preempt, mapping.id))
+    };
+    Ok(Scheduling { priority: mapping.priority, preempt })
 }
 
-fn into_comparison_fn(comparison_type: String, id: String) -> Box<dyn Fn(String) -> bool> {
+fn into_comparison_fn(comparison_type: String, id: String) -> Result<Box<dyn Fn(String) -> bool>, String> {
     match comparison_type.as_str() {
         "range" => comparison_range_builder(id),
-        _ => Box::new(move |s: String| s == id)
+        _ => Ok(Box::new(move |s: String| s == id))
     }
 }
 
-fn comparison_range_builder(range_config: String) -> Box<dyn Fn(String) -> bool> {
+pub fn comparison_range_builder(range_config: String) -> Result<Box<dyn Fn(String) -> bool>, String> {
     let ranges = range_config.split("-").collect::<Vec<&str>>().into_iter().map(|s| s.parse::<u64>()).collect::<Vec<Result<u64, ParseIntError>>>();
 
     if let [Ok(low_bound), Ok(up_bound)] = ranges[..] {
-        Box::new(move |s: String| comparison_range(s, low_bound, up_bound))
+        Ok(Box::new(move |s: String| comparison_range(s, low_bound, up_bound)))
     } else {
-        panic!("Range comparison failed, range config is not properly defined: {}", range_config);
+        Err(format!("Range comparison failed, range config is not properly defined: {}", range_config))
     }
 }
 
@@ -157,15 +234,15 @@ fn comparison_range(input: String, low_bound: u64, up_bound: u64) -> bool {
     }
 }
 
-fn condense_actions(actions: Vec<String>, category: String) -> ActionCategory {
+fn condense_actions(actions: Vec<String>, category: String) -> Result<ActionCategory, String> {
     let action_sequence = actions.iter()
         .map(|action_baby| action_birth(action_baby))
-        .collect::<Vec<Action>>();
+        .collect::<Result<Vec<Action>, String>>()?;
 
     let condensed_action: Action;
 
     if action_sequence.is_empty() {
-        panic!("At least one action is required, found 0.");
+        return Err(s!("At least one action is required, found 0."));
     } else if action_sequence.len() == 1 {
         condensed_action = action_sequence[0].clone();
     } else {
@@ -173,31 +250,195 @@ fn condense_actions(actions: Vec<String>, category: String) -> ActionCategory {
     }
 
     if category.is_empty() {
-        ActionCategory::Uncategorized(condensed_action)
+        Ok(ActionCategory::Uncategorized(condensed_action))
     } else {
-        ActionCategory::WithCategory(category.clone(), condensed_action)
+        Ok(ActionCategory::WithCategory(category.clone(), condensed_action))
     }
 }
 
-fn action_birth(action_to_map: &str) -> Action {
-    match action_to_map {
-        keydown if keydown.starts_with("kd") => Action::KeyRawDown(keydown.replace("kd", "").parse::<u16>().unwrap()),
-        keyup if keyup.starts_with("ku") => Action::KeyRawUp(keyup.replace("ku", "").parse::<u16>().unwrap()),
+pub fn action_birth(action_to_map: &str) -> Result<Action, String> {
+    let action = match action_to_map {
+        keydown if keydown.starts_with("kd") => Action::KeyRawDown(key_code_birth(&keydown[2..])?),
+        keyup if keyup.starts_with("ku") => Action::KeyRawUp(key_code_birth(&keyup[2..])?),
+        keypress if keypress.starts_with("kp") => {
+            let named = keypress[2..].starts_with(':');
+            let press = keypress[2..].trim_start_matches(':');
+            let (keys, hold_ms) = match press.rsplit_once(":") {
+                Some((keys, hold_ms)) => (keys, number_birth::<u64>(hold_ms, "hold time")?),
+                None => (press, DEFAULT_PRESS_HOLD_MS)
+            };
+            Action::Press {
+                keys: keys.split("+").map(|key| key_birth(key, named)).collect::<Result<Vec<u16>, String>>()?,
+                hold_ms
+            }
+        },
         mouse_relative if mouse_relative.starts_with("mr") => {
-            let coordinates = mouse_relative
-                .replace("mr", "")
-                .split("x")
-                .map(|xy| xy.parse::<i32>().unwrap())
-                .collect::<Vec<i32>>();
-            Action::MoveMouseOf(coordinates[0], coordinates[1])
+            let mut movement = mouse_relative[2..].split(":");
+            let (x, y) = coordinates_birth::<i32>(movement.next().unwrap_or_default())?;
+            smooth_movement_birth(MouseTarget::Relative(x, y), movement)?.unwrap_or(Action::MoveMouseOf(x, y))
+        },
+        mouse_absolute_normalized if mouse_absolute_normalized.starts_with("man") => {
+            let (x, y) = coordinates_birth::<f32>(&mouse_absolute_normalized[3..])?;
+            Action::MoveMouseToNormalized(x, y)
+        },
+        mouse_absolute if mouse_absolute.starts_with("ma") => {
+            let mut movement = mouse_absolute[2..].split(":");
+            let (x, y) = coordinates_birth::<i32>(movement.next().unwrap_or_default())?;
+            smooth_movement_birth(MouseTarget::Absolute(x, y), movement)?.unwrap_or(Action::MoveMouseTo(x, y))
+        },
+        mouse_down if mouse_down.starts_with("md") => Action::MouseDown(mouse_button_birth(&mouse_down[2..])?),
+        mouse_up if mouse_up.starts_with("mu") => Action::MouseUp(mouse_button_birth(&mouse_up[2..])?),
+        mouse_click if mouse_click.starts_with("mc") => Action::MouseClick(mouse_button_birth(&mouse_click[2..])?),
+        mouse_scroll if mouse_scroll.starts_with("ms") => {
+            let (x, y) = coordinates_birth::<i32>(&mouse_scroll[2..])?;
+            Action::ScrollOf(x, y)
+        },
+        gamepad_press if gamepad_press.starts_with("gp") => {
+            let press = gamepad_press[2..].trim_start_matches(':');
+            let (buttons, hold_ms) = match press.rsplit_once(":") {
+                Some((buttons, hold_ms)) => (buttons, number_birth::<u64>(hold_ms, "hold time")?),
+                None => (press, DEFAULT_PRESS_HOLD_MS)
+            };
+            Action::GamepadPress {
+                buttons: buttons.split("+").map(gamepad_button_birth).collect::<Result<Vec<GamepadButton>, String>>()?,
+                hold_ms
+            }
+        },
+        gamepad_down if gamepad_down.starts_with("gd") => Action::GamepadButtonDown(gamepad_button_birth(&gamepad_down[2..])?),
+        gamepad_up if gamepad_up.starts_with("gu") => Action::GamepadButtonUp(gamepad_button_birth(&gamepad_up[2..])?),
+        gamepad_stick if gamepad_stick.starts_with("gs") => {
+            let mut stick_movement = gamepad_stick[2..].trim_start_matches(':').split(":");
+            let stick = match stick_movement.next().unwrap_or_default().to_lowercase().as_str() {
+                "l" | "left" => GamepadStick::Left,
+                "r" | "right" => GamepadStick::Right,
+                wrong_stick => return Err(format!("Provided unknown gamepad stick {}", wrong_stick))
+            };
+            let position = stick_movement.next().ok_or_else(|| s!("Gamepad stick requires a position, example gs:L:0.5,-1.0"))?;
+            let (x, y) = match position.split_once(",") {
+                Some((x, y)) => (number_birth::<f32>(x, "gamepad stick position")?, number_birth::<f32>(y, "gamepad stick position")?),
+                None => return Err(format!("Provided wrong gamepad stick position {}", position))
+            };
+            let duration_ms = stick_movement.next().map(|duration_ms| number_birth::<u64>(duration_ms, "duration")).transpose()?;
+            Action::MoveStick { stick, x, y, duration_ms }
+        },
+        gamepad_trigger if gamepad_trigger.starts_with("gt") => {
+            let mut trigger_pull = gamepad_trigger[2..].trim_start_matches(':').split(":");
+            let trigger = match trigger_pull.next().unwrap_or_default().to_lowercase().as_str() {
+                "l" | "lt" | "left" => GamepadTrigger::Left,
+                "r" | "rt" | "right" => GamepadTrigger::Right,
+                wrong_trigger => return Err(format!("Provided unknown gamepad trigger {}", wrong_trigger))
+            };
+            let value = number_birth::<f32>(trigger_pull.next().ok_or_else(|| s!("Gamepad trigger requires a value, example gt:R:1.0"))?, "gamepad trigger value")?;
+            let duration_ms = trigger_pull.next().map(|duration_ms| number_birth::<u64>(duration_ms, "duration")).transpose()?;
+            Action::PullTrigger { trigger, value, duration_ms }
         },
-        wait if wait.starts_with("w") => Action::WaitFor(wait.replace("w", "").parse::<u64>().unwrap()),
+        type_text if type_text.starts_with("tx") => {
+            let (delay_ms, text) = type_text[2..].split_once(":").ok_or_else(|| s!("Type text requires a text, example tx:Hello"))?;
+            Action::TypeText(s!(text), if delay_ms.is_empty() { 0 } else { number_birth::<u64>(delay_ms, "typing delay")? })
+        },
+        wait if wait.starts_with("w") => Action::WaitFor(number_birth::<u64>(&wait.replace("w", ""), "wait")?),
         atomic_sequence if atomic_sequence.starts_with("~") =>
-            Action::AtomicSequence(atomic_sequence.split("~").skip(1).map(|matryoshka_baby| action_birth(matryoshka_baby)).collect()),
-        wrong_action_description => panic!("Provided wrong action description {}", wrong_action_description)
+            Action::AtomicSequence(atomic_sequence.split("~").skip(1).map(|matryoshka_baby| action_birth(matryoshka_baby)).collect::<Result<Vec<Action>, String>>()?),
+        wrong_action_description => return Err(format!("Provided wrong action description {}", wrong_action_description))
+    };
+    Ok(action)
+}
+
+/// Replaces `{args}` in the text to type with the text provided by the viewer
+fn with_text_arguments(action: Action, arguments: &str) -> Action {
+    match action {
+        Action::TypeText(text, delay_ms) => Action::TypeText(text.replace(TEXT_ARGUMENTS_PLACEHOLDER, arguments), delay_ms),
+        Action::Sequence(actions) => Action::Sequence(actions.into_iter().map(|action| with_text_arguments(action, arguments)).collect()),
+        Action::AtomicSequence(actions) => Action::AtomicSequence(actions.into_iter().map(|action| with_text_arguments(action, arguments)).collect()),
+        action => action
+    }
+}
+
+/// Key written as code (`kd38`) or as symbolic name (`kd:Up`)
+fn key_code_birth(key_to_map: &str) -> Result<u16, String> {
+    match key_to_map.strip_prefix(':') {
+        Some(name) => key_birth(name, true),
+        None => key_birth(key_to_map, false)
     }
 }
 
+/// Key written after a colon is a name first, without it a code first: `kd:1` is the key 1 and `kd1` the code 1
+fn key_birth(key: &str, named: bool) -> Result<u16, String> {
+    let code = if named { key_code(key) } else { key.parse::<u16>().ok().or_else(|| key_code(key)) };
+    code.ok_or_else(|| format!("Provided unknown key {}", key))
+}
+
+/// Number written in an action, e.g. the hold time of `kp:Up:500`
+fn number_birth<T: FromStr>(number_to_map: &str, what: &str) -> Result<T, String> {
+    number_to_map.parse::<T>().map_err(|_| format!("Provided wrong {} {}", what, number_to_map))
+}
+
+/// Coordinates written as `XxY`, e.g. `100x-20` or `0.5x0.5`
+fn coordinates_birth<T: FromStr>(coordinates_to_map: &str) -> Result<(T, T), String> {
+    let coordinates = coordinates_to_map
+        .split("x")
+        .map(|xy| number_birth::<T>(xy, "coordinates"))
+        .collect::<Result<Vec<T>, String>>()?;
+
+    match <[T; 2]>::try_from(coordinates) {
+        Ok([x, y]) => Ok((x, y)),
+        Err(_) => Err(format!("Provided wrong coordinates {}", coordinates_to_map))
+    }
+}
+
+/// Optional movement parameters written as `:duration[:easing[:tick rate]]`, e.g. `mr100x0:500:ease_out:120`
+fn smooth_movement_birth<'a>(target: MouseTarget, mut parameters: impl Iterator<Item = &'a str>) -> Result<Option<Action>, String> {
+    let duration_ms = match parameters.next() {
+        Some(duration_ms) => number_birth::<u64>(duration_ms, "movement duration")?,
+        None => return Ok(None)
+    };
+    let easing = match parameters.next() {
+        None | Some("linear") => Easing::Linear,
+        Some("ease_in") => Easing::EaseIn,
+        Some("ease_out") => Easing::EaseOut,
+        Some("ease_in_out") => Easing::EaseInOut,
+        Some(wrong_easing) => return Err(format!("Provided unknown easing {}", wrong_easing))
+    };
+    let tick_rate = parameters.next().map(|rate| number_birth::<u32>(rate, "tick rate")).transpose()?.unwrap_or(DEFAULT_MOVEMENT_TICK_RATE);
+
+    Ok(Some(Action::SmoothMoveMouse { target, duration_ms, easing, tick_rate }))
+}
+
+/// Mouse button written as name, e.g. `mc:Left`
+fn mouse_button_birth(button_to_map: &str) -> Result<MouseButton, String> {
+    match button_to_map.trim_start_matches(':').to_lowercase().as_str() {
+        "left" => Ok(MouseButton::Left),
+        "right" => Ok(MouseButton::Right),
+        "middle" => Ok(MouseButton::Middle),
+        "x1" | "back" => Ok(MouseButton::Extra1),
+        "x2" | "forward" => Ok(MouseButton::Extra2),
+        wrong_button => Err(format!("Provided unknown mouse button {}", wrong_button))
+    }
+}
+
+/// Xbox layout names, the D-pad directions are buttons too
+fn gamepad_button_birth(button_to_map: &str) -> Result<GamepadButton, String> {
+    let button = match button_to_map.trim_start_matches(':').to_lowercase().as_str() {
+        "a" => GamepadButton::A,
+        "b" => GamepadButton::B,
+        "x" => GamepadButton::X,
+        "y" => GamepadButton::Y,
+        "lb" => GamepadButton::LeftBumper,
+        "rb" => GamepadButton::RightBumper,
+        "back" | "select" => GamepadButton::Back,
+        "start" => GamepadButton::Start,
+        "guide" | "home" => GamepadButton::Guide,
+        "ls" | "l3" => GamepadButton::LeftThumb,
+        "rs" | "r3" => GamepadButton::RightThumb,
+        "up" => GamepadButton::DpadUp,
+        "down" => GamepadButton::DpadDown,
+        "left" => GamepadButton::DpadLeft,
+        "right" => GamepadButton::DpadRight,
+        wrong_button => return Err(format!("Provided unknown gamepad button {}", wrong_button))
+    };
+    Ok(button)
+}
+
 impl Default for Configuration {
     fn default() -> Self {
         Configuration {
@@ -207,28 +448,19 @@ impl Default for Configuration {
     }
 }
 
-impl ConfigurableEventToAction {
-    pub fn new(configuration: Configuration) -> ConfigurableEventToAction {
-        ConfigurableEventToAction { configuration }
-    }
-}
-
-impl EventToAction for ConfigurableEventToAction {
-    fn execute(&mut self, event: ChatEvent) -> Option<ActionCategory> {
-        event_to_action(event, &mut self.configuration)
-    }
-
-    fn custom_categories(&mut self) -> Vec<String> {
+impl Configuration {
+    /// Categories of the mappings, the uncategorized ones excluded
+    pub fn categories(&self) -> Vec<String> {
         let mut categories_map: HashSet<String> = HashSet::new();
 
-        for option in &self.configuration.message_options {
+        for option in &self.message_options {
             match option.actions {
                 ActionCategory::WithCategory(ref category_name, _) => { categories_map.insert(category_name.clone()); () },
                 _ => ()
             }
         }
 
-        for option in &self.configuration.action_options {
+        for option in &self.action_options {
             match option.actions {
                 ActionCategory::WithCategory(ref category_name, _) => { categories_map.insert(category_name.clone()); () },
                 _ => ()
@@ -237,25 +469,110 @@ impl EventToAction for ConfigurableEventToAction {
 
         categories_map.into_iter().collect::<Vec<String>>()
     }
+
+    /// The mappings with the same source and id keep what is left of their limit, it never grows
+    fn carry_limits(&mut self, previous: &Configuration) {
+        for option in self.message_options.iter_mut() {
+            let left = previous.message_options.iter().find(|old| old.id == option.id).and_then(|old| old.times_limit);
+            option.times_limit = lowest_limit(option.times_limit, left);
+        }
+        for option in self.action_options.iter_mut() {
+            let left = previous.action_options.iter()
+                .find(|old| old.id == option.id && old.action_name == option.action_name)
+                .and_then(|old| old.times_limit);
+            option.times_limit = lowest_limit(option.times_limit, left);
+        }
+    }
+}
+
+fn lowest_limit(limit: Option<u16>, left: Option<u16>) -> Option<u16> {
+    match (limit, left) {
+        (Some(limit), Some(left)) => Some(limit.min(left)),
+        (limit, _) => limit
+    }
+}
+
+impl ConfigurableEventToAction {
+    pub fn new(configuration: Configuration) -> ConfigurableEventToAction {
+        ConfigurableEventToAction { configuration, updates: None }
+    }
+
+    /// Event to action whose configuration can be replaced sending a new one, e.g. when the configuration file is reloaded
+    pub fn reloadable(configuration: Configuration) -> (ConfigurableEventToAction, Sender<Configuration>) {
+        let (updater, updates) = channel::<Configuration>();
+        let mut event_to_action = ConfigurableEventToAction::new(configuration);
+        event_to_action.updates = Some(updates);
+        (event_to_action, updater)
+    }
+
+    fn apply_updates(&mut self) {
+        let updates = match &self.updates {
+            Some(updates) => updates,
+            None => return
+        };
+        while let Ok(mut configuration) = updates.try_recv() {
+            configuration.carry_limits(&self.configuration);
+            self.configuration = configuration;
+            info!("Mapping configuration replaced");
+        }
+    }
+}
+
+impl EventToAction for ConfigurableEventToAction {
+    fn execute(&mut self, event: ChatEvent) -> Option<TriggeredActions> {
+        self.apply_updates();
+        event_to_triggered_actions(event, &mut self.configuration)
+    }
+
+    fn custom_categories(&mut self) -> Vec<String> {
+        self.configuration.categories()
+    }
 }
 
 impl StreamItemReceiver for ConfigurableEventToAction {
     type Item = ChatEvent;
-    type Output = Option<ActionCategory>;
-    fn receive(&mut self, event: ChatEvent) -> Option<ActionCategory> {
+    type Output = Option<TriggeredActions>;
+    fn receive(&mut self, event: ChatEvent) -> Option<TriggeredActions> {
         self.execute(event)
     }
 }
 
-fn event_to_action(event: ChatEvent, config: &mut Configuration) -> Option<ActionCategory> {
+fn event_origin(event: &ChatEvent) -> ActionOrigin {
+    match event {
+        ChatEvent::Message(message) => ActionOrigin { user: message.name.clone(), is_subscriber: message.is_subscriber, bits: 0 },
+        ChatEvent::Action(action) => ActionOrigin {
+            user: action.name.clone(),
+            is_subscriber: false,
+            bits: if action.action_name == "bits" { action.action_id.parse::<u64>().unwrap_or(0) } else { 0 }
+        }
+    }
+}
+
+fn event_to_triggered_actions(event: ChatEvent, config: &mut Configuration) -> Option<TriggeredActions> {
     let actions;
+    let scheduling;
 
     match event.clone() {
         ChatEvent::Message(message) => {
             let option = config.message_options.iter_mut()
                 .filter(|opt| opt.can_be_executed())
-                .find(|opt| opt.id == message.content)?;
-            actions = option.consume_actions();
+                .find(|opt| opt.matches(&message.content))?;
+            let arguments = match option.arguments {
+                Some(ref sanitizer) => {
+                    let text = sanitizer.sanitize(&message.content[option.id.len()..]);
+                    if text.is_none() {
+                        info!("Ignoring action {:?} from event {:?}, text not allowed", option, event);
+                    }
+                    Some(text?)
+                },
+                None => None
+            };
+            actions = match (option.consume_actions(), arguments) {
+                (ActionCategory::Uncategorized(action), Some(text)) => ActionCategory::Uncategorized(with_text_arguments(action, &text)),
+                (ActionCategory::WithCategory(category, action), Some(text)) => ActionCategory::WithCategory(category, with_text_arguments(action, &text)),
+                (actions, None) => actions
+            };
+            scheduling = option.scheduling;
             info!("Executing action {:?} from event {:?}", option, event);
         },
         ChatEvent::Action(action) => {
@@ -263,18 +580,23 @@ fn event_to_action(event: ChatEvent, config: &mut Configuration) -> Option<Actio
                 .filter(|opt| opt.can_be_executed())
                 .find(|opt| action.action_name == opt.action_name && (opt.comparison)(action.action_id.clone()))?;
             actions = option.consume_actions();
+            scheduling = option.scheduling;
             info!("Executing action {:?} from event {:?}", option, event);
         },
     }
 
-    Some(actions)
+    Some(TriggeredActions { category: actions, origin: event_origin(&event), scheduling })
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::stream_interface::events::ChatMessage;
-    use crate::{s};
+    use crate::stream_interface::events::{ChatAction, ChatMessage};
+    use std::convert::TryInto;
+
+    fn event_to_action(event: ChatEvent, config: &mut Configuration) -> Option<ActionCategory> {
+        event_to_triggered_actions(event, config).map(|triggered| triggered.category)
+    }
 
     impl Configuration {
         fn messages(message_options: Vec<ConfigOption>) -> Self {
@@ -298,7 +620,7 @@ mod tests {
             #[test] fn $fn_name() {
                 let maybe_generated = event_to_action(
                     message_event(s!("a message")),
-                    &mut Mapping { config: vec![MappingConfig { id: s!("a message"), actions: $actions, category: s!($category), source: s!("message"), comparison: s!(""), name: s!(""), limit: None } ] }.into()
+                    &mut Mapping { config: vec![MappingConfig { id: s!("a message"), actions: $actions, category: s!($category), source: s!("message"), comparison: s!(""), name: s!(""), limit: None, text: None, priority: 0, preempt: s!("") } ] }.try_into().unwrap()
                 );
 
                 assert!(maybe_generated.is_some());
@@ -313,10 +635,10 @@ mod tests {
 
     assert_return_nothing!(empty_event_empty_config_return_nothing, "", Configuration::default());
     assert_return_nothing!(event_says_up_config_not_match_return_nothing, "I said up",
-        Configuration::messages(vec![ConfigOption { id: s!(""), actions: ActionCategory::Uncategorized(Action::WaitFor(1)), times_limit: None }])
+        Configuration::messages(vec![ConfigOption { id: s!(""), actions: ActionCategory::Uncategorized(Action::WaitFor(1)), times_limit: None, arguments: None, scheduling: Scheduling::default() }])
     );
     assert_return_nothing!(empty_message_config_for_up_return_nothing, "",
-        Configuration::messages(vec![ConfigOption { id: s!("I said up"), actions: ActionCategory::Uncategorized(Action::WaitFor(1)), times_limit: None }])
+        Configuration::messages(vec![ConfigOption { id: s!("I said up"), actions: ActionCategory::Uncategorized(Action::WaitFor(1)), times_limit: None, arguments: None, scheduling: Scheduling::default() }])
     );
 
     assert_actions!(event_match_config_for_kd_number_then_key_down_raw_40,
@@ -331,10 +653,74 @@ mod tests {
      action     vec![s!("ku100")],
      returns    ActionCategory::Uncategorized(Action::KeyRawUp(100)));
 
+    assert_actions!(event_match_config_for_kp_number_and_hold_then_press,
+     action     vec![s!("kp38:200")],
+     returns    ActionCategory::Uncategorized(Action::Press { keys: vec![38], hold_ms: 200 }));
+
+    assert_actions!(event_match_config_for_kp_chord_and_hold_then_press_all_keys,
+     action     vec![s!("kp17+67:50")],
+     returns    ActionCategory::Uncategorized(Action::Press { keys: vec![17, 67], hold_ms: 50 }));
+
+    assert_actions!(event_match_config_for_kd_key_name_then_key_down_raw_code,
+     action     vec![s!("kd:Up")],
+     returns    ActionCategory::Uncategorized(Action::KeyRawDown(38)));
+
+    assert_actions!(event_match_config_for_kd_digit_key_name_then_key_down_digit_code,
+     action     vec![s!("kd:1")],
+     returns    ActionCategory::Uncategorized(Action::KeyRawDown(49)));
+
+    assert_actions!(event_match_config_for_kp_key_names_with_digit_then_press_digit_key,
+     action     vec![s!("kp:Ctrl+1")],
+     returns    ActionCategory::Uncategorized(Action::Press { keys: vec![17, 49], hold_ms: DEFAULT_PRESS_HOLD_MS }));
+
+    assert_actions!(event_match_config_for_ku_function_key_name_then_key_up_raw_code,
+     action     vec![s!("ku:F5")],
+     returns    ActionCategory::Uncategorized(Action::KeyRawUp(116)));
+
+    assert_actions!(event_match_config_for_kp_key_names_without_hold_then_press_with_default_hold,
+     action     vec![s!("kp:LeftShift+W")],
+     returns    ActionCategory::Uncategorized(Action::Press { keys: vec![160, 87], hold_ms: DEFAULT_PRESS_HOLD_MS }));
+
+    assert_actions!(event_match_config_for_kp_lowercase_key_names_and_hold_then_press,
+     action     vec![s!("kp:leftctrl+c:300")],
+     returns    ActionCategory::Uncategorized(Action::Press { keys: vec![162, 67], hold_ms: 300 }));
+
     assert_actions!(event_match_config_for_mr_coordinates_then_move_mouse,
      action     vec![s!("mr100x110")],
      returns    ActionCategory::Uncategorized(Action::MoveMouseOf(100, 110)));
 
+    assert_actions!(event_match_config_for_mr_negative_coordinates_then_move_mouse,
+     action     vec![s!("mr-100x0")],
+     returns    ActionCategory::Uncategorized(Action::MoveMouseOf(-100, 0)));
+
+    assert_actions!(event_match_config_for_ma_coordinates_then_move_mouse_to,
+     action     vec![s!("ma1920x1080")],
+     returns    ActionCategory::Uncategorized(Action::MoveMouseTo(1920, 1080)));
+
+    assert_actions!(event_match_config_for_man_coordinates_then_move_mouse_to_normalized,
+     action     vec![s!("man0.5x0.25")],
+     returns    ActionCategory::Uncategorized(Action::MoveMouseToNormalized(0.5, 0.25)));
+
+    assert_actions!(event_match_config_for_mr_coordinates_and_duration_then_smooth_linear_move,
+     action     vec![s!("mr100x0:500")],
+     returns    ActionCategory::Uncategorized(Action::SmoothMoveMouse { target: MouseTarget::Relative(100, 0), duration_ms: 500, easing: Easing::Linear, tick_rate: DEFAULT_MOVEMENT_TICK_RATE }));
+
+    assert_actions!(event_match_config_for_ma_coordinates_duration_easing_and_tick_rate_then_smooth_move_to,
+     action     vec![s!("ma960x540:300:ease_in_out:120")],
+     returns    ActionCategory::Uncategorized(Action::SmoothMoveMouse { target: MouseTarget::Absolute(960, 540), duration_ms: 300, easing: Easing::EaseInOut, tick_rate: 120 }));
+
+    assert_actions!(event_match_config_for_md_mu_button_then_mouse_down_and_up,
+     action     vec![s!("md:Left"), s!("mu:right")],
+     returns    ActionCategory::Uncategorized(Action::Sequence(vec![Action::MouseDown(MouseButton::Left), Action::MouseUp(MouseButton::Right)])));
+
+    assert_actions!(event_match_config_for_mc_extra_button_then_mouse_click,
+     action     vec![s!("mc:X2")],
+     returns    ActionCategory::Uncategorized(Action::MouseClick(MouseButton::Extra2)));
+
+    assert_actions!(event_match_config_for_ms_coordinates_then_scroll,
+     action     vec![s!("ms0x-3")],
+     returns    ActionCategory::Uncategorized(Action::ScrollOf(0, -3)));
+
     assert_actions!(event_match_config_for_multiple_kd_number_then_sequence_key_down_raw,
      action     vec![s!("kd100"), s!("kd35")],
      returns    ActionCategory::Uncategorized(Action::Sequence(vec![Action::KeyRawDown(100), Action::KeyRawDown(35)])));
@@ -357,39 +743,134 @@ mod tests {
      action     vec![s!("~w1500~ku10~w1500")],
      returns    ActionCategory::Uncategorized(Action::AtomicSequence(vec![Action::WaitFor(1500), Action::KeyRawUp(10), Action::WaitFor(1500)])));
 
+    assert_actions!(event_match_config_for_tx_text_then_type_text_without_delay,
+     action     vec![s!("tx:Hello world")],
+     returns    ActionCategory::Uncategorized(Action::TypeText(s!("Hello world"), 0)));
+
+    assert_actions!(event_match_config_for_tx_delay_and_text_then_type_text_with_delay,
+     action     vec![s!("tx40:Hello: world")],
+     returns    ActionCategory::Uncategorized(Action::TypeText(s!("Hello: world"), 40)));
+
+    assert_actions!(event_match_config_for_gp_button_then_gamepad_press_with_default_hold,
+     action     vec![s!("gp:A")],
+     returns    ActionCategory::Uncategorized(Action::GamepadPress { buttons: vec![GamepadButton::A], hold_ms: DEFAULT_PRESS_HOLD_MS }));
+
+    assert_actions!(event_match_config_for_gp_buttons_and_hold_then_gamepad_press_all_buttons,
+     action     vec![s!("gp:lb+Up:300")],
+     returns    ActionCategory::Uncategorized(Action::GamepadPress { buttons: vec![GamepadButton::LeftBumper, GamepadButton::DpadUp], hold_ms: 300 }));
+
+    assert_actions!(event_match_config_for_gd_gu_button_then_gamepad_button_down_and_up,
+     action     vec![s!("gd:Start"), s!("gu:start")],
+     returns    ActionCategory::Uncategorized(Action::Sequence(vec![Action::GamepadButtonDown(GamepadButton::Start), Action::GamepadButtonUp(GamepadButton::Start)])));
+
+    assert_actions!(event_match_config_for_gs_stick_position_and_duration_then_move_stick,
+     action     vec![s!("gs:L:0.5,-1.0:300")],
+     returns    ActionCategory::Uncategorized(Action::MoveStick { stick: GamepadStick::Left, x: 0.5, y: -1.0, duration_ms: Some(300) }));
+
+    assert_actions!(event_match_config_for_gs_stick_position_without_duration_then_stick_stays,
+     action     vec![s!("gs:R:0,1")],
+     returns    ActionCategory::Uncategorized(Action::MoveStick { stick: GamepadStick::Right, x: 0.0, y: 1.0, duration_ms: None }));
+
+    assert_actions!(event_match_config_for_gt_trigger_value_and_duration_then_pull_trigger,
+     action     vec![s!("gt:RT:1.0:200")],
+     returns    ActionCategory::Uncategorized(Action::PullTrigger { trigger: GamepadTrigger::Right, value: 1.0, duration_ms: Some(200) }));
+
+    #[test]
+    fn event_starting_with_prefix_config_then_type_text_with_message_arguments() {
+        let generated = event_to_action(message_event(s!("!name Bob")), &mut text_mapping(None).try_into().unwrap());
+
+        assert!(generated == Some(ActionCategory::Uncategorized(Action::Sequence(vec![Action::KeyRawDown(13), Action::TypeText(s!("Bob!"), 30)]))));
+    }
+
+    #[test]
+    fn event_with_prefix_not_followed_by_space_then_nothing() {
+        assert!(event_to_action(message_event(s!("!nameBob")), &mut text_mapping(None).try_into().unwrap()).is_none());
+    }
+
+    #[test]
+    fn event_arguments_with_not_allowed_characters_and_too_long_then_type_sanitized_text() {
+        let text_config = TextConfig { allowed_characters: s!("abcdefghijklmnopqrstuvwxyz"), max_length: Some(3), blocked_words: vec![] };
+        let generated = event_to_action(message_event(s!("!name b-o\nbby")), &mut text_mapping(Some(text_config)).try_into().unwrap());
+
+        assert!(generated == Some(ActionCategory::Uncategorized(Action::Sequence(vec![Action::KeyRawDown(13), Action::TypeText(s!("bob!"), 30)]))));
+    }
+
+    #[test]
+    fn event_arguments_with_blocked_word_then_nothing() {
+        let text_config = TextConfig { allowed_characters: s!(""), max_length: None, blocked_words: vec![s!("badword")] };
+
+        assert!(event_to_action(message_event(s!("!name I am BadWord")), &mut text_mapping(Some(text_config)).try_into().unwrap()).is_none());
+    }
+
+    #[test]
+    fn event_with_prefix_and_no_arguments_then_nothing() {
+        assert!(event_to_action(message_event(s!("!name   ")), &mut text_mapping(None).try_into().unwrap()).is_none());
+    }
+
+    #[test]
+    fn bits_event_then_actions_with_mapping_scheduling_and_cheerer_origin() {
+        let mut configuration: Configuration = Mapping { config: vec![MappingConfig {
+            id: s!("100-1000"), actions: vec![s!("kd13")], category: s!(""), source: s!("action"), comparison: s!("range"), name: s!("bits"), limit: None, text: None, priority: 10, preempt: s!("drop")
+        }] }.try_into().unwrap();
+        let generated = event_to_triggered_actions(ChatEvent::Action(ChatAction { name: s!("cheerer"), action_id: s!("500"), action_name: s!("bits") }), &mut configuration);
+
+        assert_eq!(generated, Some(TriggeredActions {
+            category: ActionCategory::Uncategorized(Action::KeyRawDown(13)),
+            origin: ActionOrigin { user: s!("cheerer"), is_subscriber: false, bits: 500 },
+            scheduling: Scheduling { priority: 10, preempt: Some(Preemption::Drop) }
+        }));
+    }
+
     #[test]
     fn configuration_created_without_categories_return_no_custom_categories() {
-        let mut event_to_action = ConfigurableEventToAction {
-            configuration: Configuration {
-                message_options: vec![ConfigOption { actions: ActionCategory::Uncategorized(Action::KeyRawUp(1)), id: s!(""), times_limit: None }],
-                action_options: vec![ConfigActionOption { actions: ActionCategory::Uncategorized(Action::KeyRawUp(2)), id: s!(""), action_name: s!(""), comparison: Box::new(|_: String| false), times_limit: None }]
+        let mut event_to_action = ConfigurableEventToAction::new(
+            Configuration {
+                message_options: vec![ConfigOption { actions: ActionCategory::Uncategorized(Action::KeyRawUp(1)), id: s!(""), times_limit: None, arguments: None, scheduling: Scheduling::default() }],
+                action_options: vec![ConfigActionOption { actions: ActionCategory::Uncategorized(Action::KeyRawUp(2)), id: s!(""), action_name: s!(""), comparison: Box::new(|_: String| false), times_limit: None, scheduling: Scheduling::default() }]
             }
-        };
+        );
 
         assert_eq!(event_to_action.custom_categories().len(), 0);
     }
 
     #[test]
     fn configuration_created_wit_categories_return_list_of_custom_categories() {
-        let mut event_to_action = ConfigurableEventToAction {
-            configuration: Configuration {
+        let mut event_to_action = ConfigurableEventToAction::new(
+            Configuration {
                 message_options: vec![
-                    ConfigOption { actions: ActionCategory::Uncategorized(Action::KeyRawUp(1)), id: s!(""), times_limit: None },
-                    ConfigOption { actions: ActionCategory::WithCategory(s!("1"), Action::KeyRawUp(1)), id: s!(""), times_limit: None }
+                    ConfigOption { actions: ActionCategory::Uncategorized(Action::KeyRawUp(1)), id: s!(""), times_limit: None, arguments: None, scheduling: Scheduling::default() },
+                    ConfigOption { actions: ActionCategory::WithCategory(s!("1"), Action::KeyRawUp(1)), id: s!(""), times_limit: None, arguments: None, scheduling: Scheduling::default() }
                 ],
                 action_options: vec![
-                    ConfigActionOption { actions: ActionCategory::WithCategory(s!("custom_text"), Action::KeyRawUp(2)), id: s!(""), action_name: s!(""), comparison: Box::new(|_: String| false), times_limit: None },
-                    ConfigActionOption { actions: ActionCategory::Uncategorized(Action::KeyRawUp(2)), id: s!(""), action_name: s!(""), comparison: Box::new(|_: String| false), times_limit: None }
+                    ConfigActionOption { actions: ActionCategory::WithCategory(s!("custom_text"), Action::KeyRawUp(2)), id: s!(""), action_name: s!(""), comparison: Box::new(|_: String| false), times_limit: None, scheduling: Scheduling::default() },
+                    ConfigActionOption { actions: ActionCategory::Uncategorized(Action::KeyRawUp(2)), id: s!(""), action_name: s!(""), comparison: Box::new(|_: String| false), times_limit: None, scheduling: Scheduling::default() }
                 ]
             }
-        };
+        );
 
         assert_eq!(event_to_action.custom_categories().len(), 2);
         assert!(event_to_action.custom_categories().contains(&s!("1")));
         assert!(event_to_action.custom_categories().contains(&s!("custom_text")));
     }
 
+    #[test]
+    fn reloaded_configuration_replaces_mappings_keeping_what_is_left_of_the_limits() {
+        let limited = |id: &str, key: &str, limit: u16| MappingConfig { id: s!(id), actions: vec![s!(key)], category: s!(""), source: s!("message"), comparison: s!(""), name: s!(""), limit: Some(limit), text: None, priority: 0, preempt: s!("") };
+        let (mut event_to_action, updater) = ConfigurableEventToAction::reloadable(Mapping { config: vec![limited("!jump", "kd32", 2)] }.try_into().unwrap());
+        event_to_action.execute(message_event(s!("!jump")));
+
+        updater.send(Mapping { config: vec![limited("!jump", "kd38", 5), limited("!duck", "kd40", 1)] }.try_into().unwrap()).unwrap();
+
+        assert_eq!(event_to_action.execute(message_event(s!("!jump"))).map(|triggered| triggered.category), Some(ActionCategory::Uncategorized(Action::KeyRawDown(38))));
+        assert!(event_to_action.execute(message_event(s!("!jump"))).is_none());
+        assert!(event_to_action.execute(message_event(s!("!duck"))).is_some());
+    }
+
+    fn text_mapping(text: Option<TextConfig>) -> Mapping {
+        Mapping { config: vec![MappingConfig { id: s!("!name"), actions: vec![s!("kd13"), s!("tx30:{args}!")], category: s!(""), source: s!("message"), comparison: s!("prefix"), name: s!(""), limit: None, text, priority: 0, preempt: s!("") }] }
+    }
+
     fn message_event(content: String) -> ChatEvent {
-        ChatEvent::Message(ChatMessage { name: s!(""), content, is_mod: false })
+        ChatEvent::Message(ChatMessage { name: s!(""), content, is_mod: false, is_broadcaster: false, is_subscriber: false })
     }
 }
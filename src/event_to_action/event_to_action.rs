@@ -1,7 +1,7 @@
 use crate::stream_interface::events::ChatEvent;
-use crate::actions::action::ActionCategory;
+use crate::actions::action::TriggeredActions;
 
 pub trait EventToAction {
-    fn execute(&mut self, event: ChatEvent) -> Option<ActionCategory>;
+    fn execute(&mut self, event: ChatEvent) -> Option<TriggeredActions>;
     fn custom_categories(&mut self) -> Vec<String>;
 }
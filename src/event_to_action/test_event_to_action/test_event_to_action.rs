@@ -3,7 +3,7 @@ use crate::stream_interface::events::{ChatEvent};
 use crate::utils::run_on_stream::StreamItemReceiver;
 use crate::system_input::system_input::{SystemInput};
 use crate::system_input::enigo::enigo_system_input::EnigoSystemInput;
-use crate::actions::action::{Action, ActionCategory};
+use crate::actions::action::{Action, ActionCategory, TriggeredActions};
 
 pub struct TestEventToAction {
     controller: EnigoSystemInput
@@ -16,8 +16,9 @@ pub struct TestEventToAction {
 // }
 
 impl EventToAction for TestEventToAction {
-    fn execute(&mut self, event: ChatEvent) -> Option<ActionCategory> {
+    fn execute(&mut self, event: ChatEvent) -> Option<TriggeredActions> {
         execute(event, &mut self.controller)
+            .map(|category| TriggeredActions { category, origin: Default::default(), scheduling: Default::default() })
     }
 
     fn custom_categories(&mut self) -> Vec<String> {
@@ -27,8 +28,8 @@ impl EventToAction for TestEventToAction {
 
 impl StreamItemReceiver for TestEventToAction {
     type Item = ChatEvent;
-    type Output = Option<ActionCategory>;
-    fn receive(&mut self, event: ChatEvent) -> Option<ActionCategory> {
+    type Output = Option<TriggeredActions>;
+    fn receive(&mut self, event: ChatEvent) -> Option<TriggeredActions> {
         self.execute(event)
     }
 }
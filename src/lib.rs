@@ -0,0 +1,9 @@
+//! Modules of the bot, the remote agent binary uses the input ones
+#[macro_use] extern crate log;
+
+pub mod utils;
+pub mod event_to_action;
+pub mod stream_interface;
+pub mod system_input;
+pub mod actions;
+pub mod control;
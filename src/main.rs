@@ -1,51 +1,300 @@
-use tokio_stream::{StreamExt};
-use futures::future::{join_all, join3};
-use tokio::sync::mpsc::{channel};
-use std::borrow::BorrowMut;
+use tokio::sync::mpsc::{channel, unbounded_channel, UnboundedSender};
+use tokio::sync::broadcast::Sender;
+use tokio::signal;
+use tokio::time::timeout;
+use std::collections::HashSet;
+use std::pin::Pin;
+use std::convert::TryFrom;
+use futures::stream::Stream;
 use simplelog::{SimpleLogger, LevelFilter, Config, WriteLogger, CombinedLogger, SharedLogger};
 use std::fs::File;
 use chrono::Local;
 #[macro_use] extern crate log;
-use crate::actions::action::{ActionCategory};
-use crate::stream_interface::twitch::twitch_interface::{connect_to_twitch};
-use crate::utils::run_on_stream::{run_on_stream};
-use crate::stream_interface::events::ChatEvent;
-use crate::event_to_action::configurable_event_to_action::configurable_event_to_action::{ConfigurableEventToAction};
-use crate::event_to_action::event_to_action::EventToAction;
-use crate::utils::app_config::{app_config, AppConfig};
-use crate::actions::queue::{action_queue_coordinators, redirect_action_in_queue, actions_queue};
-
-mod utils;
-mod event_to_action;
-mod stream_interface;
-mod system_input;
-mod actions;
+use twitch_commands::actions::action::{QueuedAction, TriggeredActions};
+use twitch_commands::stream_interface::events::ChatEvent;
+use twitch_commands::stream_interface::recorded_events::{record_on_stream, replay_events};
+use twitch_commands::stream_interface::twitch::twitch_interface::{connect_to_twitch, TwitchConnectOptions};
+use twitch_commands::utils::run_on_stream::{run_on_stream, control_on_stream, StreamEvent};
+use twitch_commands::event_to_action::configurable_event_to_action::configurable_event_to_action::{Configuration, ConfigurableEventToAction};
+use twitch_commands::event_to_action::event_to_action::EventToAction;
+use twitch_commands::utils::app_config::AppConfig;
+use twitch_commands::utils::cli::{cli_birth, mappings_listing, Command, USAGE};
+use twitch_commands::utils::config_validation::{validated_app_config, INVALID_CONFIG_EXIT_CODE};
+use twitch_commands::actions::queue::{action_queue_coordinators, redirect_action_in_queue, run_queues, queue_control_channel, queue_event_channel, QueueChange, QueueControl, QueueCommand};
+use twitch_commands::actions::keymap::key_code;
+use twitch_commands::actions::pause::toggle_pause_on_hotkey;
+use twitch_commands::actions::queue_factory::{queue_factory_birth, QueueSettings};
+use twitch_commands::control::control_commands::{control_commands_birth, Control, ControlEffect};
+use twitch_commands::control::reload::{load_reload, reload_on_file_change, reload_on_signal, Reload, ReloadRequest, ReloadSource};
+use twitch_commands::control::profiles::{profile_mapping, switch_profile_on_stream_category};
+use twitch_commands::control::control_api::control_api;
+use twitch_commands::control::shutdown::{shutdown_on_signals, shutdown_policy_birth, Shutdown, ShutdownMode, ShutdownReason};
+use twitch_commands::system_input::system_input_backend::{create_native_system_input, create_gamepad};
 
 #[tokio::main]
 async fn main() {
-    let configuration = app_config();
+    let cli = match cli_birth(&std::env::args().skip(1).collect::<Vec<String>>()) {
+        Ok(cli) => cli,
+        Err(e) => {
+            eprintln!("{}\n\n{}", e, USAGE);
+            std::process::exit(2);
+        }
+    };
+    if cli.command == Command::Help {
+        println!("{}", USAGE);
+        return;
+    }
+    let config_file = cli.config_file.clone();
+    let mut configuration = match validated_app_config(&config_file) {
+        Ok(configuration) => configuration,
+        Err(report) => {
+            eprintln!("{}", report);
+            std::process::exit(INVALID_CONFIG_EXIT_CODE);
+        }
+    };
+    cli.override_config(&mut configuration);
+    let mapping = match profile_mapping(&configuration.mapping, &configuration.profiles, &configuration.profile.default) {
+        Ok(mapping) => mapping,
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(INVALID_CONFIG_EXIT_CODE);
+        }
+    };
+    match cli.command {
+        Command::Validate => {
+            println!("`{}` is valid", config_file);
+            return;
+        },
+        Command::ListMappings => {
+            println!("{}", mappings_listing(&configuration.profile.default, &mapping));
+            return;
+        },
+        _ => ()
+    }
     init_logger(&configuration);
 
-    let twitch_event_stream = connect_to_twitch(configuration.twitch_stream.into()).await;
-    let stoppable_twitch_event_stream = stop_on_event!(
-        twitch_event_stream,
-        { ChatEvent::Message(ref message) => message.is_mod && message.content.to_lowercase() == "!stop", _ => false }
-    );
+    let AppConfig { twitch_stream, profile: profile_config, mut input, pause, target_window, mut gamepad, queue, control, shutdown: shutdown_config, reload, .. } = configuration;
+    let replaying = matches!(cli.command, Command::Replay(_));
+    let dry_run = cli.is_dry_run();
+    if dry_run {
+        info!("Dry run, actions are logged and not sent to the PC");
+        input.dry_run();
+        gamepad.dry_run();
+    }
+    let shared_gamepad = valid(create_gamepad(&gamepad));
+
+    let shutdown = Shutdown::default();
+    let mut shutdown_policy = valid(shutdown_policy_birth(&shutdown_config));
+    if replaying {
+        // The replay ends with the recorded events, the actions they produced still run
+        shutdown_policy.mode = ShutdownMode::Drain;
+    }
+    tokio::spawn(shutdown_on_signals(shutdown.clone()));
 
-    let mut event_to_action = ConfigurableEventToAction::new(configuration.mapping.into());
+    let (reload_notifier, mut reload_receiver) = unbounded_channel::<ReloadRequest>();
+    if !profile_config.api_address.is_empty() {
+        control_api(&profile_config.api_address, reload_notifier.clone());
+    }
+    let (control_notifier, _) = queue_control_channel();
+    let chat_control_notifier = control_notifier.clone();
+    let chat_shutdown = shutdown.clone();
+    let chat_reload_notifier = reload_notifier.clone();
+    let control_commands = valid(control_commands_birth(&control));
+    let mut accepting = true;
+    let twitch_options = TwitchConnectOptions::from(twitch_stream);
+    let chat_event_stream: Pin<Box<dyn Stream<Item = ChatEvent>>> = match cli.command {
+        Command::Replay(ref filename) => {
+            info!("Replaying the chat events recorded in {}", filename);
+            Box::pin(valid(replay_events(filename)))
+        },
+        Command::Record(ref filename) => {
+            info!("Recording the chat events in {}", filename);
+            Box::pin(valid(record_on_stream(connect_to_twitch(twitch_options.clone()).await, filename)))
+        },
+        _ => Box::pin(connect_to_twitch(twitch_options.clone()).await)
+    };
+    let controlled_chat_event_stream = control_on_stream(chat_event_stream, move |event| {
+        match control_commands.control_from_chat(&event) {
+            Some(control) => apply_control(control, &chat_control_notifier, &chat_shutdown, &chat_reload_notifier, &mut accepting),
+            None if accepting => Some(StreamEvent::Item(event)),
+            None => None
+        }
+    });
+
+    let (mut event_to_action, configuration_updater) = ConfigurableEventToAction::reloadable(valid(Configuration::try_from(mapping)));
     let custom_categories = event_to_action.custom_categories();
 
-    let (category_notifier, mut category_receiver) = channel::<ActionCategory>(100);
-    let stream_to_event_to_action = run_on_stream(stoppable_twitch_event_stream, event_to_action, category_notifier);
+    let (category_notifier, mut category_receiver) = channel::<TriggeredActions>(100);
+    let stream_to_event_to_action = async {
+        run_on_stream(controlled_chat_event_stream, event_to_action, category_notifier).await;
+        shutdown.trigger(if replaying { ShutdownReason::ReplayEnded } else { ShutdownReason::StreamEnded });
+    };
+
+    let (queue_notifiers, queue_receivers) = action_queue_coordinators(custom_categories);
+    let mut current_categories = queue_notifiers.keys().cloned().collect::<HashSet<String>>();
+    let (queue_change_notifier, queue_changes) = unbounded_channel::<QueueChange>();
+    // When it ends the queue notifiers are dropped, the queues end once they ran what they received
+    let mut action_in_queues_notifier = Box::pin(redirect_action_in_queue(&mut category_receiver, queue_notifiers, queue_changes));
+    // Paused, resumed and rejected events can be subscribed from here, the queues log them already
+    let (queue_event_notifier, _) = queue_event_channel();
+    let mut queue_factory = queue_factory_birth(QueueSettings { input, pause, target_window, queue }, shared_gamepad, control_notifier.clone(), queue_event_notifier);
+    let (queue_adder, added_queues) = unbounded_channel();
+    for (category, receiver) in queue_receivers {
+        let _ = queue_adder.send(valid(queue_factory.queue(&category, receiver)));
+    }
+    let mut actions_runners = Box::pin(run_queues(added_queues));
+
+    let toggle_hotkey = Some(&queue_factory.settings.pause.toggle_hotkey).filter(|hotkey| !hotkey.is_empty())
+        .map(|hotkey| key_code(hotkey).unwrap_or_else(|| panic!("Provided unknown pause hotkey {}", hotkey)));
+    let hotkey_control_notifier = control_notifier.clone();
+    // The optional tasks don't end the run when they are disabled
+    let pause_hotkey = async move {
+        if let Some(raw) = toggle_hotkey {
+            match create_native_system_input() {
+                Ok(native_input) => toggle_pause_on_hotkey(raw, native_input, hotkey_control_notifier).await,
+                Err(e) => error!("Pause hotkey disabled, the input of the PC can't be read. {}", e)
+            }
+        }
+        futures::future::pending::<()>().await
+    };
+
+    // A valid configuration replaces the mapping before the next event, the new categories get a queue and the removed ones
+    // end after running what they received. The other settings apply to the queues created after.
+    // Switching the profile is a reload with the mapping of that profile
+    let mut active_profile = profile_config.default.clone();
+    let reloaded_file = config_file.clone();
+    let reloads = async move {
+        while let Some(ReloadRequest { source, profile }) = reload_receiver.recv().await {
+            let profile = profile.unwrap_or_else(|| active_profile.clone());
+            info!("Reloading the configuration with profile `{}`, asked by {:?}", profile, source);
+            let Reload { configuration, categories, mut settings } = match load_reload(&reloaded_file, &profile) {
+                Ok(reload) => reload,
+                Err(e) => {
+                    error!("Configuration not reloaded, keeping the current one. {}", e);
+                    continue;
+                }
+            };
+            if dry_run {
+                settings.input.dry_run();
+            }
+            queue_factory.settings = settings;
 
-    let (mut queue_notifiers, mut queue_receivers) = action_queue_coordinators(custom_categories);
-    let action_in_queues_notifier = redirect_action_in_queue(&mut category_receiver, &mut queue_notifiers);
-    let actions_runner_queues = queue_receivers.iter_mut().map(|qr| actions_queue(qr.1.borrow_mut()));
-    let actions_runners = async move { join_all(actions_runner_queues).await; };
+            let categories = categories.into_iter().collect::<HashSet<String>>();
+            for category in categories.difference(&current_categories) {
+                let (sender, receiver) = unbounded_channel::<QueuedAction>();
+                match queue_factory.queue(category, receiver) {
+                    Ok(queue) => {
+                        let _ = queue_change_notifier.send(QueueChange::Created(category.clone(), sender));
+                        let _ = queue_adder.send(queue);
+                        info!("Queue {} created", category);
+                    },
+                    Err(e) => error!("Queue {} not created, its actions are discarded. {}", category, e)
+                }
+            }
+            let _ = configuration_updater.send(configuration);
+            for category in current_categories.difference(&categories) {
+                let _ = queue_change_notifier.send(QueueChange::Retired(category.clone()));
+                info!("Queue {} retired", category);
+            }
+            current_categories = categories;
+            active_profile = profile;
+            info!("Configuration reloaded");
+        }
+    };
+    let watch_config = async {
+        if reload.watch {
+            reload_on_file_change(&config_file, reload.watch_interval_ms, reload_notifier.clone()).await;
+        }
+        futures::future::pending::<()>().await
+    };
+    let follow_stream_category = async {
+        if !profile_config.stream_categories.is_empty() && !replaying {
+            switch_profile_on_stream_category(twitch_options, &profile_config, reload_notifier.clone()).await;
+        }
+        futures::future::pending::<()>().await
+    };
 
-    join3(stream_to_event_to_action, action_in_queues_notifier, actions_runners).await;
+    // Until the shutdown the stream runs, then it's dropped closing the connections and the intake of actions
+    tokio::select! {
+        _ = stream_to_event_to_action => (),
+        _ = shutdown.triggered() => (),
+        _ = &mut action_in_queues_notifier => (),
+        _ = &mut actions_runners => (),
+        _ = pause_hotkey => (),
+        _ = reloads => (),
+        _ = watch_config => (),
+        _ = follow_stream_category => (),
+        _ = reload_on_signal(reload_notifier.clone()) => ()
+    }
+    let reason = shutdown.reason().unwrap_or(ShutdownReason::StreamEnded);
+
+    let ending_queues = async {
+        match shutdown_policy.mode {
+            ShutdownMode::Drain => {
+                info!("Shutting down, running the actions received");
+                action_in_queues_notifier.await;
+            },
+            ShutdownMode::Clear => {
+                info!("Shutting down, discarding the actions received");
+                drop(action_in_queues_notifier);
+                notify_queues(&control_notifier, QueueControl::all(QueueCommand::Close));
+            }
+        }
+        actions_runners.await;
+    };
+    // Dropping the queues releases what they are holding
+    tokio::select! {
+        ended = timeout(shutdown_policy.timeout, ending_queues) => if ended.is_err() {
+            warn!("Queues still running after {:?}, stopping them", shutdown_policy.timeout);
+        },
+        _ = signal::ctrl_c() => warn!("Ctrl-C received again, stopping the queues")
+    }
 
     info!("End of execution");
+    std::process::exit(reason.exit_code());
+}
+
+/// Control commands written in chat, they are consumed and don't produce actions. Stop ends the stream triggering the shutdown,
+/// queue commands go to the queues, toggle accepting stops or starts the chat events producing actions and reload or switch profile
+/// ask to reload the configuration
+fn apply_control<T>(control: Control, control_notifier: &Sender<QueueControl>, shutdown: &Shutdown, reload_notifier: &UnboundedSender<ReloadRequest>, accepting: &mut bool) -> Option<StreamEvent<T>> {
+    match control.effect {
+        ControlEffect::Stop => {
+            shutdown.trigger(ShutdownReason::StopCommand);
+            return Some(StreamEvent::Stop);
+        },
+        ControlEffect::Queue(command) => notify_queues(control_notifier, QueueControl { category: control.category, command }),
+        ControlEffect::ToggleAccepting => {
+            *accepting = !*accepting;
+            info!("Chat events {}", if *accepting { "accepted again" } else { "ignored until accepting again" });
+        },
+        ControlEffect::Reload => {
+            if let Err(e) = reload_notifier.send(ReloadRequest::reload(ReloadSource::Command)) {
+                error!("apply_control::reload_error::{}", e);
+            }
+        },
+        ControlEffect::SwitchProfile(profile) => {
+            if let Err(e) = reload_notifier.send(ReloadRequest::switch_profile(&profile, ReloadSource::Command)) {
+                error!("apply_control::switch_profile_error::{}", e);
+            }
+        }
+    }
+    None
+}
+
+/// Part built from the validated configuration or from the command line, an error is a wrong value the validation missed
+/// or a file of the command line that can't be used
+fn valid<T>(built: Result<T, String>) -> T {
+    built.unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        std::process::exit(INVALID_CONFIG_EXIT_CODE);
+    })
+}
+
+fn notify_queues(control_notifier: &Sender<QueueControl>, queue_control: QueueControl) {
+    if let Err(e) = control_notifier.send(queue_control) {
+        error!("notify_queues::send_error::{}", e);
+    }
 }
 
 fn init_logger(configuration: &AppConfig) {
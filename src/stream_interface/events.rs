@@ -1,8 +1,11 @@
 use std::fmt::{Display, Formatter, Result};
+use serde::{Serialize, Deserialize};
 use crate::{s};
 
 #[derive(Debug)]
 #[derive(Clone)]
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
 pub enum ChatEvent {
     Message(ChatMessage),
     Action(ChatAction)
@@ -19,14 +22,18 @@ impl Display for ChatEvent {
 
 #[derive(Debug)]
 #[derive(Clone)]
+#[derive(Serialize, Deserialize)]
 pub struct ChatMessage {
     pub name: String,
     pub content: String,
-    pub is_mod: bool
+    pub is_mod: bool,
+    pub is_broadcaster: bool,
+    pub is_subscriber: bool
 }
 
 #[derive(Clone)]
 #[derive(Debug)]
+#[derive(Serialize, Deserialize)]
 pub struct ChatAction {
     pub name: String,
     pub action_id: String,
@@ -1,2 +1,3 @@
 pub mod events;
 pub mod twitch;
+pub mod recorded_events;
@@ -0,0 +1,118 @@
+use std::fs::{self, OpenOptions};
+use std::io;
+use std::sync::Arc;
+use futures::stream::Stream;
+use serde::{Serialize, Deserialize};
+use tokio::fs::File;
+use tokio::io::AsyncWriteExt;
+use tokio::sync::Mutex;
+use tokio::sync::mpsc::channel;
+use tokio::time::{sleep_until, Duration, Instant};
+use tokio_stream::wrappers::ReceiverStream;
+use crate::stream_interface::events::ChatEvent;
+
+// Recorded chat events are JSON objects, one per line, with the time elapsed since the recording started
+
+#[derive(Serialize, Deserialize)]
+struct RecordedEvent {
+    elapsed_ms: u64,
+    event: ChatEvent
+}
+
+/// Chat events of the stream, each one goes through once appended to the file. The error is a file that can't be opened
+pub fn record_on_stream(items: impl Stream<Item = ChatEvent>, filename: &str) -> Result<impl Stream<Item = ChatEvent>, String> {
+    let file = OpenOptions::new().create(true).append(true).open(filename)
+        .map_err(|e| format!("Can't open events file {}: {}", filename, e))?;
+    // The events are written one after the other, the lock is only there to share the file with each write
+    let file = Arc::new(Mutex::new(File::from_std(file)));
+    let started_at = Instant::now();
+    Ok(futures::StreamExt::then(items, move |event| {
+        let file = file.clone();
+        let recorded = RecordedEvent { elapsed_ms: started_at.elapsed().as_millis() as u64, event };
+        async move {
+            if let Err(e) = write_recorded_event(&mut *file.lock().await, &recorded).await {
+                error!("record_on_stream::write_error::{}", e);
+            }
+            recorded.event
+        }
+    }))
+}
+
+async fn write_recorded_event(file: &mut File, recorded: &RecordedEvent) -> io::Result<()> {
+    let mut line = serde_json::to_vec(recorded)?;
+    line.push(b'\n');
+    file.write_all(&line).await?;
+    file.flush().await
+}
+
+/// Chat events recorded in the file, sent again with the same timing. The stream ends after the last one,
+/// the error is a file that can't be read
+pub fn replay_events(filename: &str) -> Result<impl Stream<Item = ChatEvent>, String> {
+    let content = fs::read_to_string(filename).map_err(|e| format!("Can't read events file {}: {}", filename, e))?;
+    let events = content.lines()
+        .filter(|line| !line.trim().is_empty())
+        .filter_map(|line| match serde_json::from_str::<RecordedEvent>(line) {
+            Ok(recorded) => Some(recorded),
+            Err(e) => {
+                warn!("Ignored wrong recorded event {}: {}", line.trim(), e);
+                None
+            }
+        })
+        .collect::<Vec<RecordedEvent>>();
+
+    let (tx, rx) = channel::<ChatEvent>(100);
+    tokio::spawn(async move {
+        let started_at = Instant::now();
+        for RecordedEvent { elapsed_ms, event } in events {
+            sleep_until(started_at + Duration::from_millis(elapsed_ms)).await;
+            if tx.send(event).await.is_err() {
+                break;
+            }
+        }
+    });
+    Ok(ReceiverStream::new(rx))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env::temp_dir;
+    use std::io::Write;
+    use std::process::id;
+    use tokio::time::pause;
+    use tokio_stream::StreamExt;
+    use crate::stream_interface::events::{ChatAction, ChatMessage};
+    use crate::{s};
+
+    #[tokio::test]
+    async fn recorded_events_then_replayed_with_their_timing() {
+        pause();
+        let filename = temp_dir().join(format!("twitch-commands-events-{}.jsonl", id())).to_string_lossy().to_string();
+        let _ = fs::remove_file(&filename);
+        let events = vec![
+            ChatEvent::Message(ChatMessage { name: s!("viewer"), content: s!("!jump"), is_mod: false, is_broadcaster: false, is_subscriber: true }),
+            ChatEvent::Action(ChatAction { name: s!("viewer"), action_id: s!("100"), action_name: s!("bits") })
+        ];
+
+        let recorded = record_on_stream(tokio_stream::iter(events), &filename).unwrap().collect::<Vec<ChatEvent>>().await;
+        assert_eq!(recorded.len(), 2);
+        let mut file = OpenOptions::new().append(true).open(&filename).unwrap();
+        writeln!(file, "{{\"elapsed_ms\":500,\"event\":{{\"type\":\"whisper\"}}}}").unwrap();
+        writeln!(file, "{{\"elapsed_ms\":1000,\"event\":{{\"type\":\"message\",\"name\":\"mod\",\"content\":\"!stop\",\"is_mod\":true,\"is_broadcaster\":false,\"is_subscriber\":false}}}}").unwrap();
+
+        let started_at = Instant::now();
+        let replayed = replay_events(&filename).unwrap().collect::<Vec<ChatEvent>>().await;
+
+        let contents = replayed.iter().map(|event| event.to_string()).collect::<Vec<String>>();
+        assert_eq!(contents, vec![s!("viewer: !jump - mod: false"), s!("viewer: bits - 100"), s!("mod: !stop - mod: true")]);
+        assert!(started_at.elapsed() >= Duration::from_millis(1000));
+        fs::remove_file(&filename).unwrap();
+    }
+
+    #[tokio::test]
+    async fn missing_file_then_replay_error() {
+        let filename = temp_dir().join(format!("twitch-commands-missing-{}.jsonl", id())).to_string_lossy().to_string();
+
+        assert!(replay_events(&filename).err().unwrap().starts_with(&format!("Can't read events file {}", filename)));
+    }
+}
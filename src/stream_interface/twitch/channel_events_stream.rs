@@ -61,32 +61,45 @@ pub async fn create_channel_events_stream(options: TwitchConnectOptions) -> impl
     
     debug!("{}", ping_command.clone());
 
+    let (tx, rx) = channel::<ChatEvent>(100);
+    let stream_dropped = tx.clone();
+
     tokio::spawn(async move {
-        loop {
-            let jitter = thread_rng().gen_range::<u64, Range<u64>>(Range::<u64> {
-                start: 1,
-                end: 10
-            });
-            time::sleep(Duration::from_secs(30 + jitter)).await;
-            debug!("PubSub Sending Ping");
-            sender.send(Message::Text(ping_command.clone())).await.expect("Not able to send Ping to websocket PubSub");
-
-            time::sleep(Duration::from_secs(10)).await;
-            let mut ponged_data = ponged_check.lock().await;
-            if *ponged_data == true {
-                debug!("PubSub Pong checked, received within 10 seconds");
-                *ponged_data = false;
-            } else {
-                error!("PubSub disconnected because Pong not sent within 10 seconds of Ping, reconnection not implemented");
-                break;
+        let keep_alive = async {
+            loop {
+                let jitter = thread_rng().gen_range::<u64, Range<u64>>(Range::<u64> {
+                    start: 1,
+                    end: 10
+                });
+                time::sleep(Duration::from_secs(30 + jitter)).await;
+                debug!("PubSub Sending Ping");
+                sender.send(Message::Text(ping_command.clone())).await.expect("Not able to send Ping to websocket PubSub");
+
+                time::sleep(Duration::from_secs(10)).await;
+                let mut ponged_data = ponged_check.lock().await;
+                if *ponged_data {
+                    debug!("PubSub Pong checked, received within 10 seconds");
+                    *ponged_data = false;
+                } else {
+                    error!("PubSub disconnected because Pong not sent within 10 seconds of Ping, reconnection not implemented");
+                    break;
+                }
+            }
+        };
+
+        // The websocket is closed when the stream is dropped
+        tokio::select! {
+            _ = keep_alive => (),
+            _ = stream_dropped.closed() => {
+                debug!("PubSub stream dropped, closing the connection");
+                if let Err(e) = sender.close().await {
+                    debug!("PubSub close error {}", e);
+                }
             }
         }
     });
-    let (tx, rx) = channel::<ChatEvent>(100);
-
     tokio::spawn(async move {
-        loop {
-            let msg = receiver.next().await.unwrap();
+        while let Some(msg) = receiver.next().await {
             match msg {
                 Ok(Message::Text(text)) => {
                     let result = Response::parse(&text).unwrap();
@@ -107,11 +120,14 @@ pub async fn create_channel_events_stream(options: TwitchConnectOptions) -> impl
                                 }, ..
                             } = reply.borrow() {
                                 info!("Redeemed {:?}!", title);
-                                tx.send(ChatEvent::Action(ChatAction {
+                                let sent = tx.send(ChatEvent::Action(ChatAction {
                                     name: user_name.to_owned(),
                                     action_name: "reward_redeem".to_string(),
                                     action_id: title.to_string()
-                                })).await.unwrap();
+                                })).await;
+                                if sent.is_err() {
+                                    break;
+                                }
                             }
                         },
                         Response::Message {
@@ -126,11 +142,14 @@ pub async fn create_channel_events_stream(options: TwitchConnectOptions) -> impl
                                 }, ..
                             } = reply.borrow() {
                                 info!("Received {:?} bits!", bits_used);
-                                tx.send(ChatEvent::Action(ChatAction {
+                                let sent = tx.send(ChatEvent::Action(ChatAction {
                                     name: user_name.to_owned(),
                                     action_name: "bits".to_string(),
                                     action_id: bits_used.to_string()
-                                })).await.unwrap();
+                                })).await;
+                                if sent.is_err() {
+                                    break;
+                                }
                             }
                         },
                         Response::Response(tr) => {
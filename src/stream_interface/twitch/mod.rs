@@ -1,3 +1,4 @@
 pub mod twitch_interface;
 pub mod user_id_from_login_name;
-pub mod channel_events_stream;
\ No newline at end of file
+pub mod channel_events_stream;
+pub mod stream_category;
\ No newline at end of file
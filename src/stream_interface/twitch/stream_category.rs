@@ -0,0 +1,61 @@
+use curl::easy::{Easy, List};
+use serde::{Deserialize};
+use crate::stream_interface::twitch::twitch_interface::TwitchConnectOptions;
+
+pub const DEFAULT_HELIX_BASE_URL: &str = "https://api.twitch.tv/helix";
+
+#[derive(Deserialize)]
+struct TwitchStreamsResponse {
+    data: Vec<TwitchStreamsResponseData>
+}
+
+#[derive(Deserialize)]
+struct TwitchStreamsResponseData {
+    game_name: String
+}
+
+/// Category (game) of the channel stream, None when the channel is offline
+pub fn stream_category(helix_base_url: &str, options: &TwitchConnectOptions) -> Result<Option<String>, String> {
+    let mut easy = Easy::new();
+    easy.url(format!("{}/streams?user_login={}", helix_base_url.trim_end_matches('/'), options.channel).as_ref())
+        .map_err(|e| format!("wrong Helix url {}", e))?;
+
+    let mut list = List::new();
+    list.append(format!("Authorization: Bearer {}", options.token).as_ref()).map_err(|e| e.to_string())?;
+    list.append(format!("Client-Id: {}", options.client_id).as_ref()).map_err(|e| e.to_string())?;
+    easy.http_headers(list).map_err(|e| e.to_string())?;
+
+    let mut body = Vec::new();
+    {
+        let mut transfer = easy.transfer();
+        transfer.write_function(|data| {
+            body.extend_from_slice(data);
+            Ok(data.len())
+        }).map_err(|e| e.to_string())?;
+        transfer.perform().map_err(|e| format!("Helix request failed {}", e))?;
+    }
+
+    category_from_streams_response(&body)
+}
+
+fn category_from_streams_response(body: &[u8]) -> Result<Option<String>, String> {
+    let TwitchStreamsResponse { data: streams } = serde_json::from_slice::<TwitchStreamsResponse>(body)
+        .map_err(|e| format!("unexpected Helix response {}", e))?;
+    Ok(streams.into_iter().next().map(|stream| stream.game_name))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{s};
+
+    #[test]
+    fn live_stream_then_its_category_and_offline_then_none() {
+        let live = br#"{"data":[{"id":"1","user_login":"channel","game_id":"27471","game_name":"Minecraft","type":"live"}],"pagination":{}}"#;
+        let offline = br#"{"data":[],"pagination":{}}"#;
+
+        assert_eq!(category_from_streams_response(live), Ok(Some(s!("Minecraft"))));
+        assert_eq!(category_from_streams_response(offline), Ok(None));
+        assert!(category_from_streams_response(b"Unauthorized").is_err());
+    }
+}
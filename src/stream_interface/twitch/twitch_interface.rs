@@ -25,6 +25,7 @@ async fn create_messages_stream(options: TwitchConnectOptions) -> impl Stream<It
         TwitchIRCClient::<TCPTransport, StaticLoginCredentials>::new(config);
     
     let (tx, rx) = channel::<ChatEvent>(100);
+    let stream_dropped = tx.clone();
 
     tokio::spawn(async move {
         let join_handle = tokio::spawn(async move {
@@ -37,12 +38,18 @@ async fn create_messages_stream(options: TwitchConnectOptions) -> impl Stream<It
                         _ => has_mod_tag = false
                     };
 
-                    let has_broadcaster_badge = msg.badges.into_iter().any(|badge| badge.name == "broadcaster");
-                    tx.send(ChatEvent::Message(ChatMessage {
+                    let has_broadcaster_badge = msg.badges.iter().any(|badge| badge.name == "broadcaster");
+                    let has_subscriber_badge = msg.badges.iter().any(|badge| badge.name == "subscriber" || badge.name == "founder");
+                    let sent = tx.send(ChatEvent::Message(ChatMessage {
                         name: s!(msg.sender.name),
                         content: s!(msg.message_text),
-                        is_mod: has_mod_tag || has_broadcaster_badge
-                    })).await.unwrap();
+                        is_mod: has_mod_tag || has_broadcaster_badge,
+                        is_broadcaster: has_broadcaster_badge,
+                        is_subscriber: has_subscriber_badge
+                    })).await;
+                    if sent.is_err() {
+                        break;
+                    }
                 } else {
                     debug!("Irc message that is not a Private Message {:?}", message);
                 }
@@ -51,7 +58,11 @@ async fn create_messages_stream(options: TwitchConnectOptions) -> impl Stream<It
 
         client.join(channel_to_log_into);
 
-        join_handle.await.unwrap();
+        // The client is dropped when the stream is, closing the connection
+        tokio::select! {
+            joined = join_handle => joined.unwrap(),
+            _ = stream_dropped.closed() => debug!("Chat stream dropped, closing the IRC connection")
+        }
     });
     
     ReceiverStream::new(rx)
@@ -1,8 +1,15 @@
-use crate::system_input::system_input::SystemInput;
+use crate::system_input::system_input::{SystemInput, MouseButton, FocusedWindow, mark_injected_input, physical_idle_time};
 use tokio::time::{Duration, sleep, Sleep};
 use std::mem::{transmute_copy, size_of, transmute};
 use winapi::um::winuser::*;
+use winapi::um::sysinfoapi::GetTickCount;
+use winapi::um::processthreadsapi::OpenProcess;
+use winapi::um::winbase::QueryFullProcessImageNameW;
+use winapi::um::handleapi::CloseHandle;
+use winapi::um::winnt::PROCESS_QUERY_LIMITED_INFORMATION;
+use winapi::shared::minwindef::{DWORD, FALSE};
 use winapi::ctypes::{c_int, c_ulong};
+use winapi::shared::windef::{POINT, HWND};
 
 pub struct CustomSystemInput {
 }
@@ -18,10 +25,86 @@ impl SystemInput for CustomSystemInput {
         unsafe { GetKeyState(VK_LBUTTON) & 0x80 != 0 }
     }
 
+    fn idle_time(&self) -> Option<Duration> {
+        let mut last_input = LASTINPUTINFO { cbSize: size_of::<LASTINPUTINFO>() as u32, dwTime: 0 };
+        if unsafe { GetLastInputInfo(&mut last_input) } == 0 {
+            return None;
+        }
+        let idle_ms = unsafe { GetTickCount() }.wrapping_sub(last_input.dwTime);
+        physical_idle_time(Duration::from_millis(idle_ms as u64))
+    }
+
+    fn is_key_down(&self, raw: u16) -> bool {
+        unsafe { GetAsyncKeyState(raw as c_int) as u16 & 0x8000 != 0 }
+    }
+
+    fn focused_window(&self) -> Option<FocusedWindow> {
+        let window = unsafe { GetForegroundWindow() };
+        if window.is_null() {
+            return Some(FocusedWindow::default());
+        }
+        let mut title = [0u16; 512];
+        let title_length = unsafe { GetWindowTextW(window, title.as_mut_ptr(), title.len() as c_int) };
+
+        Some(FocusedWindow {
+            title: String::from_utf16_lossy(&title[..title_length.max(0) as usize]),
+            process: window_process_name(window).unwrap_or_default()
+        })
+    }
+
+    fn mouse_position(&self) -> Option<(i32, i32)> {
+        let mut point = POINT { x: 0, y: 0 };
+        if unsafe { GetCursorPos(&mut point) } != 0 {
+            Some((point.x, point.y))
+        } else {
+            None
+        }
+    }
+
     fn move_mouse_of(&mut self, x: i32, y: i32) {
         mouse_event(MOUSEEVENTF_MOVE, 0, x, y);
     }
 
+    fn move_mouse_to(&mut self, x: i32, y: i32) {
+        let (width, height) = unsafe { (GetSystemMetrics(SM_CXSCREEN), GetSystemMetrics(SM_CYSCREEN)) };
+        self.move_mouse_to_normalized(x as f32 / (width - 1).max(1) as f32, y as f32 / (height - 1).max(1) as f32);
+    }
+
+    fn move_mouse_to_normalized(&mut self, x: f32, y: f32) {
+        let absolute_x = (x.clamp(0.0, 1.0) * 65535.0).round() as i32;
+        let absolute_y = (y.clamp(0.0, 1.0) * 65535.0).round() as i32;
+        mouse_event(MOUSEEVENTF_MOVE | MOUSEEVENTF_ABSOLUTE, 0, absolute_x, absolute_y);
+    }
+
+    fn mouse_down(&mut self, button: MouseButton) {
+        match button {
+            MouseButton::Left => mouse_event(MOUSEEVENTF_LEFTDOWN, 0, 0, 0),
+            MouseButton::Right => mouse_event(MOUSEEVENTF_RIGHTDOWN, 0, 0, 0),
+            MouseButton::Middle => mouse_event(MOUSEEVENTF_MIDDLEDOWN, 0, 0, 0),
+            MouseButton::Extra1 => mouse_event(MOUSEEVENTF_XDOWN, XBUTTON1 as u32, 0, 0),
+            MouseButton::Extra2 => mouse_event(MOUSEEVENTF_XDOWN, XBUTTON2 as u32, 0, 0)
+        }
+    }
+
+    fn mouse_up(&mut self, button: MouseButton) {
+        match button {
+            MouseButton::Left => mouse_event(MOUSEEVENTF_LEFTUP, 0, 0, 0),
+            MouseButton::Right => mouse_event(MOUSEEVENTF_RIGHTUP, 0, 0, 0),
+            MouseButton::Middle => mouse_event(MOUSEEVENTF_MIDDLEUP, 0, 0, 0),
+            MouseButton::Extra1 => mouse_event(MOUSEEVENTF_XUP, XBUTTON1 as u32, 0, 0),
+            MouseButton::Extra2 => mouse_event(MOUSEEVENTF_XUP, XBUTTON2 as u32, 0, 0)
+        }
+    }
+
+    fn scroll(&mut self, x: i32, y: i32) {
+        if y != 0 {
+            mouse_event(MOUSEEVENTF_WHEEL, (y * WHEEL_DELTA as i32) as u32, 0, 0);
+        }
+        if x != 0 {
+            mouse_event(MOUSEEVENTF_HWHEEL, (x * WHEEL_DELTA as i32) as u32, 0, 0);
+        }
+    }
+
     fn delay_for(&mut self, ms: u64) -> Sleep {
         sleep(Duration::from_millis(ms))
     }
@@ -35,15 +118,42 @@ impl SystemInput for CustomSystemInput {
         let extended_flag = extended_flag_if_necessary(raw);
         keybd_event(KEYEVENTF_SCANCODE | KEYEVENTF_KEYUP | extended_flag, 0, key_to_scancode(raw));
     }
+
+    fn type_char(&mut self, character: char) {
+        let mut utf16_units = [0; 2];
+        for unit in character.encode_utf16(&mut utf16_units).iter() {
+            keybd_event(KEYEVENTF_UNICODE, 0, *unit);
+            keybd_event(KEYEVENTF_UNICODE | KEYEVENTF_KEYUP, 0, *unit);
+        }
+    }
 }
 
 fn extended_flag_if_necessary(virtual_keycode: u16) -> c_ulong {
     match virtual_keycode {
-        n if n >= 37 && n <= 40 => KEYEVENTF_EXTENDEDKEY,
+        n if n >= 33 && n <= 40 => KEYEVENTF_EXTENDEDKEY,
+        45 | 46 | 91 | 92 | 111 | 144 | 163 | 165 => KEYEVENTF_EXTENDEDKEY,
         _ => 0
     }
 }
 
+fn window_process_name(window: HWND) -> Option<String> {
+    let mut process_id: DWORD = 0;
+    unsafe { GetWindowThreadProcessId(window, &mut process_id) };
+    let process = unsafe { OpenProcess(PROCESS_QUERY_LIMITED_INFORMATION, FALSE, process_id) };
+    if process.is_null() {
+        return None;
+    }
+
+    let mut path = [0u16; 1024];
+    let mut path_length = path.len() as DWORD;
+    let found = unsafe { QueryFullProcessImageNameW(process, 0, path.as_mut_ptr(), &mut path_length) } != 0;
+    unsafe { CloseHandle(process) };
+    if !found {
+        return None;
+    }
+    String::from_utf16_lossy(&path[..path_length as usize]).rsplit('\\').next().map(String::from)
+}
+
 fn key_to_scancode(virtual_keycode: u16) -> u16 {
     unsafe { MapVirtualKeyW(virtual_keycode as u32, 0) as u16 }
 }
@@ -62,6 +172,7 @@ fn keybd_event(flags: u32, vk: u16, scan: u16) {
         },
     };
     unsafe { SendInput(1, &mut input as LPINPUT, size_of::<INPUT>() as c_int) };
+    mark_injected_input();
 }
 
 fn mouse_event(flags: u32, data: u32, dx: i32, dy: i32) {
@@ -79,4 +190,5 @@ fn mouse_event(flags: u32, data: u32, dx: i32, dy: i32) {
         },
     };
     unsafe { SendInput(1, &mut input as LPINPUT, size_of::<INPUT>() as c_int) };
+    mark_injected_input();
 }
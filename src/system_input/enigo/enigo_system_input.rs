@@ -1,5 +1,5 @@
-use enigo::{Enigo, MouseControllable, KeyboardControllable, Key};
-use crate::system_input::system_input::SystemInput;
+use enigo::{Enigo, MouseControllable, KeyboardControllable, Key, MouseButton as EnigoMouseButton};
+use crate::system_input::system_input::{SystemInput, MouseButton, FocusedWindow};
 use tokio::time::{Duration, sleep, Sleep};
 
 pub struct EnigoSystemInput {
@@ -7,21 +7,82 @@ pub struct EnigoSystemInput {
 }
 
 impl EnigoSystemInput {
-    #[allow(dead_code)]
     pub fn new() -> EnigoSystemInput {
         EnigoSystemInput { enigo: Enigo::new() }
     }
 }
 
+/// Enigo knows the size of the main display, needed by the normalised moves, only on Windows and macOS
+pub const SUPPORTS_NORMALIZED_MOVES: bool = cfg!(any(windows, target_os = "macos"));
+
 impl SystemInput for EnigoSystemInput {
+    /// Enigo can only send input, it can't read the mouse state
     fn is_mouse_left_down(&self) -> bool {
-        unimplemented!()
+        false
+    }
+
+    fn idle_time(&self) -> Option<Duration> {
+        None
+    }
+
+    fn is_key_down(&self, _raw: u16) -> bool {
+        false
+    }
+
+    fn focused_window(&self) -> Option<FocusedWindow> {
+        None
+    }
+
+    fn mouse_position(&self) -> Option<(i32, i32)> {
+        None
     }
 
     fn move_mouse_of(&mut self, x: i32, y: i32) {
         self.enigo.mouse_move_relative(x, y);
     }
 
+    fn move_mouse_to(&mut self, x: i32, y: i32) {
+        self.enigo.mouse_move_to(x, y);
+    }
+
+    #[cfg(any(windows, target_os = "macos"))]
+    fn move_mouse_to_normalized(&mut self, x: f32, y: f32) {
+        let (width, height) = Enigo::main_display_size();
+        self.enigo.mouse_move_to(
+            (x.clamp(0.0, 1.0) * width.saturating_sub(1) as f32).round() as i32,
+            (y.clamp(0.0, 1.0) * height.saturating_sub(1) as f32).round() as i32
+        );
+    }
+
+    /// Rejected when validating the configuration, see SUPPORTS_NORMALIZED_MOVES
+    #[cfg(not(any(windows, target_os = "macos")))]
+    fn move_mouse_to_normalized(&mut self, x: f32, y: f32) {
+        warn!("Enigo input doesn't know the screen size on this OS, normalised move to {}x{} ignored", x, y);
+    }
+
+    fn mouse_down(&mut self, button: MouseButton) {
+        match enigo_mouse_button(button) {
+            Some(enigo_button) => self.enigo.mouse_down(enigo_button),
+            None => warn!("Enigo input doesn't support mouse button {:?}", button)
+        }
+    }
+
+    fn mouse_up(&mut self, button: MouseButton) {
+        match enigo_mouse_button(button) {
+            Some(enigo_button) => self.enigo.mouse_up(enigo_button),
+            None => warn!("Enigo input doesn't support mouse button {:?}", button)
+        }
+    }
+
+    fn scroll(&mut self, x: i32, y: i32) {
+        if y != 0 {
+            self.enigo.mouse_scroll_y(-y);
+        }
+        if x != 0 {
+            self.enigo.mouse_scroll_x(x);
+        }
+    }
+
     fn delay_for(&mut self, ms: u64) -> Sleep {
         sleep(Duration::from_millis(ms))
     }
@@ -33,4 +94,17 @@ impl SystemInput for EnigoSystemInput {
     fn key_up(&mut self, raw: u16) {
         self.enigo.key_up(Key::Raw(raw))
     }
+
+    fn type_char(&mut self, character: char) {
+        self.enigo.key_sequence(&character.to_string())
+    }
+}
+
+fn enigo_mouse_button(button: MouseButton) -> Option<EnigoMouseButton> {
+    match button {
+        MouseButton::Left => Some(EnigoMouseButton::Left),
+        MouseButton::Right => Some(EnigoMouseButton::Right),
+        MouseButton::Middle => Some(EnigoMouseButton::Middle),
+        MouseButton::Extra1 | MouseButton::Extra2 => None
+    }
 }
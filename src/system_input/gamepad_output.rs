@@ -0,0 +1,55 @@
+use std::sync::{Arc, Mutex};
+
+#[derive(Debug)]
+#[derive(Clone, Copy)]
+#[derive(PartialEq, Eq, Hash)]
+pub enum GamepadButton {
+    A,
+    B,
+    X,
+    Y,
+    LeftBumper,
+    RightBumper,
+    Back,
+    Start,
+    Guide,
+    LeftThumb,
+    RightThumb,
+    DpadUp,
+    DpadDown,
+    DpadLeft,
+    DpadRight
+}
+
+#[derive(Debug)]
+#[derive(Clone, Copy)]
+#[derive(PartialEq, Eq, Hash)]
+pub enum GamepadStick {
+    Left,
+    Right
+}
+
+#[derive(Debug)]
+#[derive(Clone, Copy)]
+#[derive(PartialEq, Eq, Hash)]
+pub enum GamepadTrigger {
+    Left,
+    Right
+}
+
+/// Virtual controller for games that can't be played with keyboard and mouse
+pub trait GamepadOutput {
+    fn button_down(&mut self, button: GamepadButton);
+    fn button_up(&mut self, button: GamepadButton);
+    /// Stick position between -1.0 and 1.0, positive x is right and positive y is up
+    fn move_stick(&mut self, stick: GamepadStick, x: f32, y: f32);
+    /// Trigger pressure between 0.0 (released) and 1.0 (fully pulled)
+    fn pull_trigger(&mut self, trigger: GamepadTrigger, value: f32);
+}
+
+/// Every category queue drives the same gamepad, the game has to see a single controller
+pub type SharedGamepad = Arc<Mutex<Box<dyn GamepadOutput + Send>>>;
+
+pub fn shared_gamepad(gamepad_output: Box<dyn GamepadOutput + Send>) -> SharedGamepad {
+    Arc::new(Mutex::new(gamepad_output))
+}
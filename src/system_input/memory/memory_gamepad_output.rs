@@ -0,0 +1,71 @@
+use std::sync::{Arc, Mutex};
+use crate::system_input::gamepad_output::{GamepadOutput, GamepadButton, GamepadStick, GamepadTrigger};
+
+#[derive(Debug)]
+#[derive(Clone)]
+#[derive(PartialEq)]
+pub enum GamepadEvent {
+    ButtonDown(GamepadButton),
+    ButtonUp(GamepadButton),
+    Stick(GamepadStick, f32, f32),
+    Trigger(GamepadTrigger, f32)
+}
+
+/// Events sent to the gamepad, it can be shared to read them while the gamepad is used
+#[derive(Clone)]
+#[derive(Default)]
+pub struct GamepadLog {
+    events: Arc<Mutex<Vec<GamepadEvent>>>
+}
+
+impl GamepadLog {
+    #[cfg(test)]
+    pub fn events(&self) -> Vec<GamepadEvent> {
+        self.events.lock().unwrap().clone()
+    }
+
+    fn push(&self, event: GamepadEvent) {
+        self.events.lock().unwrap().push(event);
+    }
+}
+
+/// Gamepad that doesn't touch the PC, it logs the events for dry runs. Only the tests keep them in a log
+pub struct MemoryGamepadOutput {
+    log: Option<GamepadLog>
+}
+
+impl MemoryGamepadOutput {
+    pub fn new(log: GamepadLog) -> MemoryGamepadOutput {
+        MemoryGamepadOutput { log: Some(log) }
+    }
+
+    /// Events only logged, for the dry runs
+    pub fn logging() -> MemoryGamepadOutput {
+        MemoryGamepadOutput { log: None }
+    }
+
+    fn record(&mut self, event: GamepadEvent) {
+        info!("Dry run gamepad input: {:?}", event);
+        if let Some(ref log) = self.log {
+            log.push(event);
+        }
+    }
+}
+
+impl GamepadOutput for MemoryGamepadOutput {
+    fn button_down(&mut self, button: GamepadButton) {
+        self.record(GamepadEvent::ButtonDown(button));
+    }
+
+    fn button_up(&mut self, button: GamepadButton) {
+        self.record(GamepadEvent::ButtonUp(button));
+    }
+
+    fn move_stick(&mut self, stick: GamepadStick, x: f32, y: f32) {
+        self.record(GamepadEvent::Stick(stick, x, y));
+    }
+
+    fn pull_trigger(&mut self, trigger: GamepadTrigger, value: f32) {
+        self.record(GamepadEvent::Trigger(trigger, value));
+    }
+}
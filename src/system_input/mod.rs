@@ -1,3 +1,13 @@
 pub mod system_input;
+pub mod gamepad_output;
+pub mod system_input_backend;
 pub mod enigo;
+pub mod recording;
+pub mod memory;
+pub mod remote;
+#[cfg(windows)]
 pub mod custom_system_input;
+#[cfg(target_os = "linux")]
+pub mod xtest;
+#[cfg(target_os = "linux")]
+pub mod uinput;
@@ -0,0 +1 @@
+pub mod recording_system_input;
@@ -0,0 +1,144 @@
+use crate::system_input::system_input::{SystemInput, MouseButton, FocusedWindow};
+use tokio::time::{Duration, Instant, sleep, Sleep};
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::sync::{Arc, Mutex};
+
+#[derive(Debug)]
+#[derive(Clone)]
+#[derive(PartialEq)]
+pub enum RecordedInput {
+    KeyDown(u16),
+    KeyUp(u16),
+    TypeChar(char),
+    MoveMouseOf(i32, i32),
+    MoveMouseTo(i32, i32),
+    MoveMouseToNormalized(f32, f32),
+    MouseDown(MouseButton),
+    MouseUp(MouseButton),
+    Scroll(i32, i32)
+}
+
+/// Inputs recorded with the time elapsed since the recording started, it can be shared to read it while recording
+#[derive(Clone)]
+#[derive(Default)]
+pub struct Timeline {
+    entries: Arc<Mutex<Vec<(Duration, RecordedInput)>>>
+}
+
+impl Timeline {
+    #[cfg(test)]
+    pub fn entries(&self) -> Vec<(Duration, RecordedInput)> {
+        self.entries.lock().unwrap().clone()
+    }
+
+    #[cfg(test)]
+    pub fn inputs(&self) -> Vec<RecordedInput> {
+        self.entries().into_iter().map(|(_, input)| input).collect()
+    }
+
+    fn push(&self, elapsed: Duration, input: RecordedInput) {
+        self.entries.lock().unwrap().push((elapsed, input));
+    }
+}
+
+/// Input that doesn't touch the PC, it logs what would have been sent. Only the tests keep it in a timeline
+pub struct RecordingSystemInput {
+    started_at: Instant,
+    timeline: Option<Timeline>,
+    file: Option<File>
+}
+
+impl RecordingSystemInput {
+    pub fn new(timeline: Timeline) -> RecordingSystemInput {
+        RecordingSystemInput { started_at: Instant::now(), timeline: Some(timeline), file: None }
+    }
+
+    /// Input only logged, for the dry runs
+    pub fn logging() -> RecordingSystemInput {
+        RecordingSystemInput { started_at: Instant::now(), timeline: None, file: None }
+    }
+
+    /// Input logged and appended to a file, one input per line
+    pub fn with_file(filename: &str) -> Result<RecordingSystemInput, String> {
+        let file = OpenOptions::new().create(true).append(true).open(filename)
+            .map_err(|e| format!("Can't open recording file {}: {}", filename, e))?;
+        Ok(RecordingSystemInput { started_at: Instant::now(), timeline: None, file: Some(file) })
+    }
+
+    fn record(&mut self, input: RecordedInput) {
+        let elapsed = self.started_at.elapsed();
+        info!("Dry run input at {}ms: {:?}", elapsed.as_millis(), input);
+
+        if let Some(ref mut file) = self.file {
+            if let Err(e) = writeln!(file, "{} {:?}", elapsed.as_millis(), input) {
+                error!("recording_system_input::write_error::{}", e);
+            }
+        }
+        if let Some(ref timeline) = self.timeline {
+            timeline.push(elapsed, input);
+        }
+    }
+}
+
+impl SystemInput for RecordingSystemInput {
+    fn is_mouse_left_down(&self) -> bool {
+        false
+    }
+
+    fn idle_time(&self) -> Option<Duration> {
+        None
+    }
+
+    fn is_key_down(&self, _raw: u16) -> bool {
+        false
+    }
+
+    fn focused_window(&self) -> Option<FocusedWindow> {
+        None
+    }
+
+    fn mouse_position(&self) -> Option<(i32, i32)> {
+        None
+    }
+
+    fn move_mouse_of(&mut self, x: i32, y: i32) {
+        self.record(RecordedInput::MoveMouseOf(x, y));
+    }
+
+    fn move_mouse_to(&mut self, x: i32, y: i32) {
+        self.record(RecordedInput::MoveMouseTo(x, y));
+    }
+
+    fn move_mouse_to_normalized(&mut self, x: f32, y: f32) {
+        self.record(RecordedInput::MoveMouseToNormalized(x, y));
+    }
+
+    fn mouse_down(&mut self, button: MouseButton) {
+        self.record(RecordedInput::MouseDown(button));
+    }
+
+    fn mouse_up(&mut self, button: MouseButton) {
+        self.record(RecordedInput::MouseUp(button));
+    }
+
+    fn scroll(&mut self, x: i32, y: i32) {
+        self.record(RecordedInput::Scroll(x, y));
+    }
+
+    fn delay_for(&mut self, ms: u64) -> Sleep {
+        sleep(Duration::from_millis(ms))
+    }
+
+    fn key_down(&mut self, raw: u16) {
+        self.record(RecordedInput::KeyDown(raw));
+    }
+
+    fn key_up(&mut self, raw: u16) {
+        self.record(RecordedInput::KeyUp(raw));
+    }
+
+    fn type_char(&mut self, character: char) {
+        self.record(RecordedInput::TypeChar(character));
+    }
+}
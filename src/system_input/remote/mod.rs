@@ -0,0 +1,3 @@
+pub mod protocol;
+pub mod remote_system_input;
+pub mod remote_agent;
@@ -0,0 +1,75 @@
+use std::io;
+use std::io::Write;
+use serde::{Serialize, Deserialize};
+use serde::de::DeserializeOwned;
+use crate::system_input::system_input::{MouseButton, FocusedWindow};
+
+// Messages of the remote input protocol are JSON objects, one per line
+
+/// From the bot to the agent, the first one has to be Hello
+#[derive(Debug)]
+#[derive(Clone)]
+#[derive(PartialEq)]
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ClientMessage {
+    Hello { token: String },
+    KeyDown { raw: u16 },
+    KeyUp { raw: u16 },
+    TypeChar { character: char },
+    MoveMouseOf { x: i32, y: i32 },
+    MoveMouseTo { x: i32, y: i32 },
+    MoveMouseToNormalized { x: f32, y: f32 },
+    MouseDown { button: MouseButton },
+    MouseUp { button: MouseButton },
+    Scroll { x: i32, y: i32 },
+    Ping { id: u64 }
+}
+
+/// From the agent to the bot, State is pushed periodically after Welcome
+#[derive(Debug)]
+#[derive(Clone)]
+#[derive(PartialEq)]
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum AgentMessage {
+    Welcome,
+    Denied,
+    Pong { id: u64 },
+    State { mouse_left_down: bool, mouse_position: Option<(i32, i32)>, idle_ms: Option<u64>, focused_window: Option<FocusedWindow> }
+}
+
+pub fn write_message<T: Serialize>(stream: &mut impl Write, message: &T) -> io::Result<()> {
+    let mut line = serde_json::to_vec(message)?;
+    line.push(b'\n');
+    stream.write_all(&line)
+}
+
+pub fn parse_message<T: DeserializeOwned>(line: &[u8]) -> Option<T> {
+    match serde_json::from_slice::<T>(line) {
+        Ok(message) => Some(message),
+        Err(e) => {
+            warn!("Ignored wrong remote input message {}: {}", String::from_utf8_lossy(line).trim(), e);
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{s};
+
+    #[test]
+    fn messages_written_as_json_lines() {
+        let mut written = Vec::new();
+        write_message(&mut written, &ClientMessage::MouseDown { button: MouseButton::Extra1 }).unwrap();
+        write_message(&mut written, &ClientMessage::Hello { token: s!("secret") }).unwrap();
+
+        assert_eq!(String::from_utf8(written.clone()).unwrap(), "{\"type\":\"mouse_down\",\"button\":\"Extra1\"}\n{\"type\":\"hello\",\"token\":\"secret\"}\n");
+        assert_eq!(written.split(|byte| *byte == b'\n').filter(|line| !line.is_empty()).filter_map(parse_message::<ClientMessage>).collect::<Vec<_>>(), vec![
+            ClientMessage::MouseDown { button: MouseButton::Extra1 },
+            ClientMessage::Hello { token: s!("secret") }
+        ]);
+    }
+}
@@ -0,0 +1,237 @@
+use std::collections::HashMap;
+use std::io::{BufRead, BufReader};
+use std::net::{Shutdown, TcpListener, TcpStream};
+use std::sync::mpsc::{channel, sync_channel, Receiver, Sender, SyncSender, RecvTimeoutError, TrySendError};
+use std::thread;
+use std::time::{Duration, Instant};
+use crate::system_input::system_input::SystemInput;
+use crate::system_input::remote::protocol::{ClientMessage, AgentMessage, write_message, parse_message};
+
+const STATE_PUSH_INTERVAL_MS: u64 = 100;
+const HELLO_TIMEOUT_MS: u64 = 5000;
+const WRITE_TIMEOUT_MS: u64 = 1000;
+/// Messages waiting for a client, a client that stopped reading fills it and is disconnected
+const OUTGOING_CAPACITY: usize = 64;
+
+enum AgentEvent {
+    Connected(u64, TcpStream, SyncSender<AgentMessage>),
+    Received(u64, ClientMessage),
+    Disconnected(u64)
+}
+
+struct Client {
+    stream: TcpStream,
+    /// Messages written by the writer thread of the client, the input is never blocked by the socket
+    outgoing: SyncSender<AgentMessage>,
+    /// KeyDown and MouseDown not released yet by the client
+    held_inputs: Vec<ClientMessage>
+}
+
+/// Executes on this PC the input of the bots connected with the right token.
+/// Clients are read on their own threads, the input is sent only from the calling thread
+pub fn serve(listener: TcpListener, token: String, input_system: &mut dyn SystemInput) {
+    let (events, received_events) = channel::<AgentEvent>();
+    thread::spawn(move || accept_clients(listener, token, events));
+
+    let mut clients = HashMap::<u64, Client>::new();
+    let mut last_state_push = Instant::now();
+    loop {
+        match received_events.recv_timeout(Duration::from_millis(STATE_PUSH_INTERVAL_MS)) {
+            Ok(AgentEvent::Connected(id, stream, outgoing)) => {
+                info!("Remote client {} connected from {:?}", id, stream.peer_addr());
+                clients.insert(id, Client { stream, outgoing, held_inputs: Vec::new() });
+            },
+            Ok(AgentEvent::Received(id, message)) => {
+                if let Some(client) = clients.get_mut(&id) {
+                    execute(client, message, input_system);
+                }
+            },
+            Ok(AgentEvent::Disconnected(id)) => {
+                if let Some(mut client) = clients.remove(&id) {
+                    info!("Remote client {} disconnected, releasing {:?}", id, client.held_inputs);
+                    release_held_inputs(&mut client, input_system);
+                }
+            },
+            Err(RecvTimeoutError::Timeout) => (),
+            Err(RecvTimeoutError::Disconnected) => break
+        }
+
+        if last_state_push.elapsed() >= Duration::from_millis(STATE_PUSH_INTERVAL_MS) {
+            push_state(&clients, input_system);
+            last_state_push = Instant::now();
+        }
+    }
+
+    for (_, mut client) in clients.drain() {
+        release_held_inputs(&mut client, input_system);
+    }
+}
+
+fn accept_clients(listener: TcpListener, token: String, events: Sender<AgentEvent>) {
+    for (id, stream) in (0u64..).zip(listener.incoming()) {
+        match stream {
+            Ok(stream) => {
+                let token = token.clone();
+                let events = events.clone();
+                thread::spawn(move || read_client(id, stream, &token, events));
+            },
+            Err(e) => error!("remote_agent::accept_error::{}", e)
+        }
+    }
+}
+
+fn read_client(id: u64, stream: TcpStream, token: &str, events: Sender<AgentEvent>) {
+    let mut writer = stream;
+    let mut reader = match writer.try_clone() {
+        Ok(stream) => BufReader::new(stream),
+        Err(e) => {
+            error!("remote_agent::clone_error::{}", e);
+            return;
+        }
+    };
+    if !authenticate(&mut reader, &mut writer, token) {
+        return;
+    }
+    let _ = writer.set_nodelay(true);
+    let _ = writer.set_write_timeout(Some(Duration::from_millis(WRITE_TIMEOUT_MS)));
+    let (outgoing, messages) = sync_channel::<AgentMessage>(OUTGOING_CAPACITY);
+    match writer.try_clone() {
+        Ok(stream) => thread::spawn(move || write_client(stream, messages)),
+        Err(e) => {
+            error!("remote_agent::clone_error::{}", e);
+            return;
+        }
+    };
+    if events.send(AgentEvent::Connected(id, writer, outgoing)).is_err() {
+        return;
+    }
+
+    let mut line = Vec::new();
+    loop {
+        line.clear();
+        match reader.read_until(b'\n', &mut line) {
+            Ok(0) | Err(_) => break,
+            Ok(_) => {
+                let received = parse_message::<ClientMessage>(&line).map(|message| events.send(AgentEvent::Received(id, message)));
+                if let Some(Err(_)) = received {
+                    return;
+                }
+            }
+        }
+    }
+    let _ = events.send(AgentEvent::Disconnected(id));
+}
+
+/// Writes the messages of the client until it's disconnected, a failed write disconnects it
+fn write_client(mut stream: TcpStream, messages: Receiver<AgentMessage>) {
+    for message in messages {
+        if let Err(e) = write_message(&mut stream, &message) {
+            warn!("Remote client {:?} disconnected, writing failed: {}", stream.peer_addr(), e);
+            let _ = stream.shutdown(Shutdown::Both);
+            return;
+        }
+    }
+}
+
+/// A client with too many messages waiting stopped reading, it's disconnected and its reader thread releases what it holds
+fn send(client: &Client, message: AgentMessage) {
+    if let Err(TrySendError::Full(_)) = client.outgoing.try_send(message) {
+        warn!("Remote client {:?} not reading its messages, disconnecting it", client.stream.peer_addr());
+        let _ = client.stream.shutdown(Shutdown::Both);
+    }
+}
+
+fn authenticate(reader: &mut BufReader<TcpStream>, writer: &mut TcpStream, token: &str) -> bool {
+    let mut line = Vec::new();
+    let _ = reader.get_ref().set_read_timeout(Some(Duration::from_millis(HELLO_TIMEOUT_MS)));
+    let hello = reader.read_until(b'\n', &mut line).ok().and_then(|_| parse_message::<ClientMessage>(&line));
+    let _ = reader.get_ref().set_read_timeout(None);
+
+    match hello {
+        Some(ClientMessage::Hello { token: received_token }) if is_same_token(&received_token, token) => {
+            write_message(writer, &AgentMessage::Welcome).is_ok()
+        },
+        _ => {
+            warn!("Remote client from {:?} refused, wrong token", writer.peer_addr());
+            let _ = write_message(writer, &AgentMessage::Denied);
+            false
+        }
+    }
+}
+
+fn execute(client: &mut Client, message: ClientMessage, input_system: &mut dyn SystemInput) {
+    match message {
+        ClientMessage::KeyDown { raw } => {
+            input_system.key_down(raw);
+            hold(client, message);
+        },
+        ClientMessage::KeyUp { raw } => {
+            input_system.key_up(raw);
+            client.held_inputs.retain(|held_input| *held_input != ClientMessage::KeyDown { raw });
+        },
+        ClientMessage::MouseDown { button } => {
+            input_system.mouse_down(button);
+            hold(client, message);
+        },
+        ClientMessage::MouseUp { button } => {
+            input_system.mouse_up(button);
+            client.held_inputs.retain(|held_input| *held_input != ClientMessage::MouseDown { button });
+        },
+        ClientMessage::TypeChar { character } => input_system.type_char(character),
+        ClientMessage::MoveMouseOf { x, y } => input_system.move_mouse_of(x, y),
+        ClientMessage::MoveMouseTo { x, y } => input_system.move_mouse_to(x, y),
+        ClientMessage::MoveMouseToNormalized { x, y } => input_system.move_mouse_to_normalized(x, y),
+        ClientMessage::Scroll { x, y } => input_system.scroll(x, y),
+        ClientMessage::Ping { id } => send(client, AgentMessage::Pong { id }),
+        ClientMessage::Hello { .. } => warn!("Remote client said hello twice")
+    }
+}
+
+fn hold(client: &mut Client, message: ClientMessage) {
+    if !client.held_inputs.contains(&message) {
+        client.held_inputs.push(message);
+    }
+}
+
+fn release_held_inputs(client: &mut Client, input_system: &mut dyn SystemInput) {
+    for held_input in client.held_inputs.drain(..).rev() {
+        match held_input {
+            ClientMessage::KeyDown { raw } => input_system.key_up(raw),
+            ClientMessage::MouseDown { button } => input_system.mouse_up(button),
+            _ => ()
+        }
+    }
+}
+
+fn push_state(clients: &HashMap<u64, Client>, input_system: &dyn SystemInput) {
+    if clients.is_empty() {
+        return;
+    }
+    let state = AgentMessage::State {
+        mouse_left_down: input_system.is_mouse_left_down(),
+        mouse_position: input_system.mouse_position(),
+        idle_ms: input_system.idle_time().map(|idle_time| idle_time.as_millis() as u64),
+        focused_window: input_system.focused_window()
+    };
+    for client in clients.values() {
+        send(client, state.clone());
+    }
+}
+
+/// Same time whatever the position of the first wrong character, so the token can't be guessed by timing
+pub fn is_same_token(received: &str, expected: &str) -> bool {
+    received.len() == expected.len()
+        && received.bytes().zip(expected.bytes()).fold(0, |difference, (a, b)| difference | (a ^ b)) == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn token_compared_whole() {
+        assert!(is_same_token("secret", "secret"));
+        assert!(!is_same_token("secreT", "secret"));
+        assert!(!is_same_token("secret2", "secret"));
+    }
+}
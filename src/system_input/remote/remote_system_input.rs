@@ -0,0 +1,358 @@
+use std::collections::HashMap;
+use std::io::{BufRead, BufReader};
+use std::net::{Shutdown, TcpStream, ToSocketAddrs};
+use std::sync::{Arc, Mutex};
+use std::sync::mpsc::{channel, Receiver, RecvTimeoutError, Sender};
+use std::thread;
+use std::time::Instant;
+use tokio::time::{Duration, sleep, Sleep};
+use crate::system_input::system_input::{SystemInput, MouseButton, FocusedWindow};
+use crate::system_input::remote::protocol::{ClientMessage, AgentMessage, write_message, parse_message};
+
+const CONNECT_TIMEOUT_MS: u64 = 2000;
+const RECONNECT_INTERVAL_MS: u64 = 1000;
+const PING_INTERVAL_MS: u64 = 1000;
+
+/// Last state pushed by the agent, valid while connected
+#[derive(Default)]
+struct AgentState {
+    connected: bool,
+    mouse_left_down: bool,
+    mouse_position: Option<(i32, i32)>,
+    idle: Option<(Duration, Instant)>,
+    focused_window: Option<FocusedWindow>,
+    latency: Option<Duration>,
+    pings: HashMap<u64, Instant>
+}
+
+/// Input sent to the remote agent running on the game PC.
+/// The connection is made and written on its own thread so the queue never waits for the network,
+/// input is lost while the agent is not connected and reconnection is attempted in the background
+pub struct RemoteSystemInput {
+    messages: Sender<ClientMessage>,
+    state: Arc<Mutex<AgentState>>
+}
+
+impl RemoteSystemInput {
+    pub fn new(address: &str, token: &str) -> RemoteSystemInput {
+        let (messages, received_messages) = channel::<ClientMessage>();
+        let state = Arc::new(Mutex::new(AgentState::default()));
+        let (address, token, connection_state) = (address.to_string(), token.to_string(), state.clone());
+        thread::spawn(move || keep_connected(address, token, received_messages, connection_state));
+        RemoteSystemInput { messages, state }
+    }
+
+    /// Round trip time of the last ping, None until the agent answers
+    pub fn latency(&self) -> Option<Duration> {
+        self.state.lock().unwrap().latency
+    }
+
+    fn send(&mut self, message: ClientMessage) {
+        if !self.state.lock().unwrap().connected {
+            warn!("Remote agent not connected, input {:?} lost", message);
+            return;
+        }
+        if let Err(e) = self.messages.send(message) {
+            warn!("Remote agent connection ended, input {:?} lost", e.0);
+        }
+    }
+}
+
+/// Connects to the agent and writes the messages to it until the RemoteSystemInput is dropped, reconnecting when the connection is lost
+fn keep_connected(address: String, token: String, messages: Receiver<ClientMessage>, state: Arc<Mutex<AgentState>>) {
+    loop {
+        match connect(&address, &token) {
+            Ok((stream, reader)) => {
+                info!("Connected to remote agent {}", address);
+                *state.lock().unwrap() = AgentState { connected: true, ..AgentState::default() };
+                let reader_state = state.clone();
+                let reader = thread::spawn(move || read_agent(reader, reader_state));
+                let dropped = write_agent(&stream, &messages, &state);
+                let _ = stream.shutdown(Shutdown::Both);
+                let _ = reader.join();
+                if dropped {
+                    return;
+                }
+            },
+            Err(e) => warn!("Can't connect to remote agent {}, retrying in {}ms: {}", address, RECONNECT_INTERVAL_MS, e)
+        }
+
+        let retry_at = Instant::now() + Duration::from_millis(RECONNECT_INTERVAL_MS);
+        loop {
+            match messages.recv_timeout(retry_at.saturating_duration_since(Instant::now())) {
+                Ok(message) => warn!("Remote agent not connected, input {:?} lost", message),
+                Err(RecvTimeoutError::Timeout) => break,
+                Err(RecvTimeoutError::Disconnected) => return
+            }
+        }
+    }
+}
+
+fn connect(address: &str, token: &str) -> Result<(TcpStream, BufReader<TcpStream>), String> {
+    let socket_address = address.to_socket_addrs().map_err(|e| e.to_string())?
+        .next().ok_or(format!("no address for {}", address))?;
+    let mut stream = TcpStream::connect_timeout(&socket_address, Duration::from_millis(CONNECT_TIMEOUT_MS)).map_err(|e| e.to_string())?;
+    let _ = stream.set_nodelay(true);
+    write_message(&mut stream, &ClientMessage::Hello { token: token.to_string() }).map_err(|e| e.to_string())?;
+
+    let mut reader = BufReader::new(stream.try_clone().map_err(|e| e.to_string())?);
+    let mut line = Vec::new();
+    let _ = stream.set_read_timeout(Some(Duration::from_millis(CONNECT_TIMEOUT_MS)));
+    reader.read_until(b'\n', &mut line).map_err(|e| e.to_string())?;
+    let _ = stream.set_read_timeout(None);
+
+    match parse_message::<AgentMessage>(&line) {
+        Some(AgentMessage::Welcome) => Ok((stream, reader)),
+        Some(AgentMessage::Denied) => Err(String::from("the agent refused the token")),
+        _ => Err(String::from("the agent didn't answer"))
+    }
+}
+
+/// Writes the messages and a ping every PING_INTERVAL_MS until the connection is lost, true when the RemoteSystemInput is dropped
+fn write_agent(mut stream: &TcpStream, messages: &Receiver<ClientMessage>, state: &Arc<Mutex<AgentState>>) -> bool {
+    let mut next_ping = Instant::now();
+    let mut ping_id = 0u64;
+    loop {
+        let message = match messages.recv_timeout(next_ping.saturating_duration_since(Instant::now())) {
+            Ok(message) => message,
+            Err(RecvTimeoutError::Timeout) => {
+                ping_id += 1;
+                next_ping = Instant::now() + Duration::from_millis(PING_INTERVAL_MS);
+                state.lock().unwrap().pings.insert(ping_id, Instant::now());
+                ClientMessage::Ping { id: ping_id }
+            },
+            Err(RecvTimeoutError::Disconnected) => return true
+        };
+
+        if !state.lock().unwrap().connected {
+            warn!("Remote agent connection lost, input {:?} lost", message);
+            return false;
+        }
+        if let Err(e) = write_message(&mut stream, &message) {
+            warn!("Remote agent connection lost, input {:?} lost: {}", message, e);
+            state.lock().unwrap().connected = false;
+            return false;
+        }
+    }
+}
+
+fn read_agent(mut reader: BufReader<TcpStream>, state: Arc<Mutex<AgentState>>) {
+    let mut line = Vec::new();
+    loop {
+        line.clear();
+        match reader.read_until(b'\n', &mut line) {
+            Ok(0) | Err(_) => break,
+            Ok(_) => match parse_message::<AgentMessage>(&line) {
+                Some(AgentMessage::State { mouse_left_down, mouse_position, idle_ms, focused_window }) => {
+                    let mut state = state.lock().unwrap();
+                    state.mouse_left_down = mouse_left_down;
+                    state.mouse_position = mouse_position;
+                    state.idle = idle_ms.map(|idle_ms| (Duration::from_millis(idle_ms), Instant::now()));
+                    state.focused_window = focused_window;
+                },
+                Some(AgentMessage::Pong { id }) => {
+                    let mut state = state.lock().unwrap();
+                    if let Some(sent_at) = state.pings.remove(&id) {
+                        let latency = sent_at.elapsed();
+                        debug!("Remote agent latency {}ms", latency.as_millis());
+                        state.latency = Some(latency);
+                    }
+                },
+                _ => ()
+            }
+        }
+    }
+
+    warn!("Remote agent disconnected");
+    state.lock().unwrap().connected = false;
+}
+
+impl SystemInput for RemoteSystemInput {
+    fn is_mouse_left_down(&self) -> bool {
+        let state = self.state.lock().unwrap();
+        state.connected && state.mouse_left_down
+    }
+
+    /// Idle time of the game PC, grown by the time since the agent measured it
+    fn idle_time(&self) -> Option<Duration> {
+        let state = self.state.lock().unwrap();
+        state.idle.filter(|_| state.connected).map(|(idle, measured_at)| idle + measured_at.elapsed())
+    }
+
+    /// Hotkeys are read on this PC, the agent doesn't send the keyboard state
+    fn is_key_down(&self, _raw: u16) -> bool {
+        false
+    }
+
+    fn focused_window(&self) -> Option<FocusedWindow> {
+        let state = self.state.lock().unwrap();
+        if state.connected { state.focused_window.clone() } else { Some(FocusedWindow::default()) }
+    }
+
+    fn mouse_position(&self) -> Option<(i32, i32)> {
+        let state = self.state.lock().unwrap();
+        state.mouse_position.filter(|_| state.connected)
+    }
+
+    fn move_mouse_of(&mut self, x: i32, y: i32) {
+        self.send(ClientMessage::MoveMouseOf { x, y });
+    }
+
+    fn move_mouse_to(&mut self, x: i32, y: i32) {
+        self.send(ClientMessage::MoveMouseTo { x, y });
+    }
+
+    fn move_mouse_to_normalized(&mut self, x: f32, y: f32) {
+        self.send(ClientMessage::MoveMouseToNormalized { x, y });
+    }
+
+    fn mouse_down(&mut self, button: MouseButton) {
+        self.send(ClientMessage::MouseDown { button });
+    }
+
+    fn mouse_up(&mut self, button: MouseButton) {
+        self.send(ClientMessage::MouseUp { button });
+    }
+
+    fn scroll(&mut self, x: i32, y: i32) {
+        self.send(ClientMessage::Scroll { x, y });
+    }
+
+    fn delay_for(&mut self, ms: u64) -> Sleep {
+        sleep(Duration::from_millis(ms))
+    }
+
+    fn key_down(&mut self, raw: u16) {
+        self.send(ClientMessage::KeyDown { raw });
+    }
+
+    fn key_up(&mut self, raw: u16) {
+        self.send(ClientMessage::KeyUp { raw });
+    }
+
+    fn type_char(&mut self, character: char) {
+        self.send(ClientMessage::TypeChar { character });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::TcpListener;
+    use crate::system_input::recording::recording_system_input::{RecordingSystemInput, RecordedInput, Timeline};
+    use crate::system_input::remote::remote_agent::serve;
+    use crate::{s};
+
+    #[test]
+    fn input_sent_on_loopback_executed_by_agent() {
+        let (address, timeline) = start_agent(TcpListener::bind("127.0.0.1:0").unwrap());
+        let mut remote = connected_remote(&address);
+
+        remote.key_down(38);
+        remote.key_up(38);
+        remote.mouse_down(MouseButton::Right);
+        remote.mouse_up(MouseButton::Right);
+        remote.type_char('é');
+
+        assert_eq!(wait_for_inputs(&timeline, 5), vec![
+            RecordedInput::KeyDown(38), RecordedInput::KeyUp(38),
+            RecordedInput::MouseDown(MouseButton::Right), RecordedInput::MouseUp(MouseButton::Right), RecordedInput::TypeChar('é')
+        ]);
+    }
+
+    #[test]
+    fn wrong_token_then_agent_refuses_input() {
+        let (address, timeline) = start_agent(TcpListener::bind("127.0.0.1:0").unwrap());
+        let mut remote = RemoteSystemInput::new(&address, "guess");
+        thread::sleep(Duration::from_millis(100));
+
+        remote.key_down(38);
+
+        assert!(!remote.state.lock().unwrap().connected);
+        assert_eq!(timeline.inputs(), vec![]);
+    }
+
+    #[test]
+    fn connection_dropped_then_agent_releases_held_inputs() {
+        let (address, timeline) = start_agent(TcpListener::bind("127.0.0.1:0").unwrap());
+        let mut remote = connected_remote(&address);
+
+        remote.key_down(38);
+        remote.mouse_down(MouseButton::Left);
+        remote.key_down(40);
+        remote.key_up(40);
+        drop(remote);
+
+        assert_eq!(wait_for_inputs(&timeline, 6)[4..].to_vec(), vec![RecordedInput::MouseUp(MouseButton::Left), RecordedInput::KeyUp(38)]);
+    }
+
+    #[test]
+    fn agent_started_later_then_client_reconnects() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+        drop(listener);
+        let mut remote = RemoteSystemInput::new(&address, "secret");
+        remote.key_down(1);
+
+        let (_, timeline) = start_agent(TcpListener::bind(&address).unwrap());
+        wait_connected(&remote);
+        remote.key_down(38);
+
+        assert_eq!(wait_for_inputs(&timeline, 1), vec![RecordedInput::KeyDown(38)]);
+    }
+
+    #[test]
+    fn agent_answers_pings_then_latency_measured() {
+        let (address, _) = start_agent(TcpListener::bind("127.0.0.1:0").unwrap());
+        let remote = connected_remote(&address);
+
+        let started_at = Instant::now();
+        while remote.latency().is_none() && started_at.elapsed() < Duration::from_secs(2) {
+            thread::sleep(Duration::from_millis(10));
+        }
+
+        assert!(remote.latency().is_some());
+    }
+
+    fn start_agent(listener: TcpListener) -> (String, Timeline) {
+        let address = listener.local_addr().unwrap().to_string();
+        let timeline = Timeline::default();
+        let agent_timeline = timeline.clone();
+        thread::spawn(move || serve(listener, s!("secret"), &mut RecordingSystemInput::new(agent_timeline)));
+        (address, timeline)
+    }
+
+    #[test]
+    fn agent_not_answering_then_input_not_blocked() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let mut remote = RemoteSystemInput::new(&listener.local_addr().unwrap().to_string(), "secret");
+
+        let started_at = Instant::now();
+        remote.key_down(38);
+        remote.key_up(38);
+
+        assert!(started_at.elapsed() < Duration::from_millis(100));
+        assert!(!remote.state.lock().unwrap().connected);
+    }
+
+    fn connected_remote(address: &str) -> RemoteSystemInput {
+        let remote = RemoteSystemInput::new(address, "secret");
+        wait_connected(&remote);
+        remote
+    }
+
+    fn wait_connected(remote: &RemoteSystemInput) {
+        let started_at = Instant::now();
+        while !remote.state.lock().unwrap().connected && started_at.elapsed() < Duration::from_secs(3) {
+            thread::sleep(Duration::from_millis(10));
+        }
+    }
+
+    fn wait_for_inputs(timeline: &Timeline, count: usize) -> Vec<RecordedInput> {
+        let started_at = Instant::now();
+        while timeline.inputs().len() < count && started_at.elapsed() < Duration::from_secs(2) {
+            thread::sleep(Duration::from_millis(10));
+        }
+        timeline.inputs()
+    }
+}
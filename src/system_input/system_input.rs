@@ -1,9 +1,83 @@
-use tokio::time::Sleep;
+use tokio::time::{Duration, Sleep};
+use serde::{Serialize, Deserialize};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Input on the PC this close to an injected input is taken for the injected one
+const INJECTED_INPUT_TOLERANCE_MS: u64 = 50;
+
+static LAST_INJECTED_INPUT_MS: AtomicU64 = AtomicU64::new(0);
+static LAST_PHYSICAL_INPUT_MS: AtomicU64 = AtomicU64::new(0);
+
+#[derive(Debug)]
+#[derive(Clone, Copy)]
+#[derive(PartialEq, Eq, Hash)]
+#[derive(Serialize, Deserialize)]
+pub enum MouseButton {
+    Left,
+    Right,
+    Middle,
+    Extra1,
+    Extra2
+}
+
+/// Window receiving the input, process is the executable name
+#[derive(Debug)]
+#[derive(Clone)]
+#[derive(PartialEq)]
+#[derive(Default)]
+#[derive(Serialize, Deserialize)]
+pub struct FocusedWindow {
+    pub title: String,
+    pub process: String
+}
 
 pub trait SystemInput {
     fn is_mouse_left_down(&self) -> bool;
+    /// Time since the last keyboard or mouse input made on the PC, not sent by the application, if the backend can know it
+    fn idle_time(&self) -> Option<Duration>;
+    /// Whether a key is held on the PC right now
+    fn is_key_down(&self, raw: u16) -> bool;
+    /// Window receiving the input, None if the backend can't know it
+    fn focused_window(&self) -> Option<FocusedWindow>;
+    /// Current mouse position in screen pixels, if the backend can know it
+    fn mouse_position(&self) -> Option<(i32, i32)>;
     fn move_mouse_of(&mut self, x: i32, y: i32);
+    /// Absolute move in screen pixels
+    fn move_mouse_to(&mut self, x: i32, y: i32);
+    /// Absolute move in coordinates normalised between 0.0 and 1.0 of the screen size
+    fn move_mouse_to_normalized(&mut self, x: f32, y: f32);
+    fn mouse_down(&mut self, button: MouseButton);
+    fn mouse_up(&mut self, button: MouseButton);
+    /// Scroll in wheel notches, positive x scrolls right and positive y scrolls up
+    fn scroll(&mut self, x: i32, y: i32);
     fn delay_for(&mut self, ms: u64) -> Sleep;
     fn key_down(&mut self, raw: u16);
     fn key_up(&mut self, raw: u16);
+    /// Types a character independently from the keyboard layout
+    fn type_char(&mut self, character: char);
+}
+
+/// Called by the native backends on every injected input, so it isn't taken for input of the streamer
+pub fn mark_injected_input() {
+    LAST_INJECTED_INPUT_MS.store(now_ms(), Ordering::Relaxed);
+}
+
+/// The idle time of the system is reset by injected input too,
+/// the last input counts as physical only when it came after the last injected one
+pub fn physical_idle_time(system_idle_time: Duration) -> Option<Duration> {
+    let now = now_ms();
+    let last_input = now.saturating_sub(system_idle_time.as_millis() as u64);
+    if last_input > LAST_INJECTED_INPUT_MS.load(Ordering::Relaxed) + INJECTED_INPUT_TOLERANCE_MS {
+        LAST_PHYSICAL_INPUT_MS.fetch_max(last_input, Ordering::Relaxed);
+    }
+
+    match LAST_PHYSICAL_INPUT_MS.load(Ordering::Relaxed) {
+        0 => None,
+        last_physical_input => Some(Duration::from_millis(now.saturating_sub(last_physical_input)))
+    }
+}
+
+fn now_ms() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|elapsed| elapsed.as_millis() as u64).unwrap_or(0)
 }
@@ -0,0 +1,97 @@
+use crate::system_input::system_input::SystemInput;
+use crate::system_input::enigo::enigo_system_input::EnigoSystemInput;
+use crate::system_input::recording::recording_system_input::RecordingSystemInput;
+use crate::system_input::remote::remote_system_input::RemoteSystemInput;
+#[cfg(windows)]
+use crate::system_input::custom_system_input::custom_system_input::CustomSystemInput as NativeSystemInput;
+#[cfg(target_os = "linux")]
+use crate::system_input::xtest::xtest_system_input::XTestSystemInput as NativeSystemInput;
+use crate::system_input::gamepad_output::{GamepadOutput, SharedGamepad, shared_gamepad};
+use crate::system_input::memory::memory_gamepad_output::MemoryGamepadOutput;
+#[cfg(target_os = "linux")]
+use crate::system_input::uinput::uinput_gamepad_output::UinputGamepadOutput;
+use crate::utils::app_config::{InputConfig, GamepadConfig};
+
+/// Input backend of a category as written in the configuration
+#[derive(Debug)]
+#[derive(Clone, Copy)]
+#[derive(PartialEq)]
+pub enum InputBackend {
+    Native,
+    Enigo,
+    Recording,
+    Remote
+}
+
+/// Gamepad backend as written in the configuration, uinput exists on Linux only
+#[derive(Debug)]
+#[derive(Clone, Copy)]
+#[derive(PartialEq)]
+pub enum GamepadBackend {
+    #[cfg(target_os = "linux")]
+    Uinput,
+    Memory
+}
+
+/// Empty backend name is the native backend of the OS
+pub fn input_backend_birth(backend_to_map: &str) -> Result<InputBackend, String> {
+    match backend_to_map {
+        "" | "native" => Ok(InputBackend::Native),
+        "enigo" => Ok(InputBackend::Enigo),
+        "recording" => Ok(InputBackend::Recording),
+        "remote" => Ok(InputBackend::Remote),
+        unknown_backend => Err(format!("Provided unknown input backend {}, it can be native, enigo, recording or remote", unknown_backend))
+    }
+}
+
+/// Empty backend name is no gamepad
+pub fn gamepad_backend_birth(backend_to_map: &str) -> Result<Option<GamepadBackend>, String> {
+    match backend_to_map {
+        "" => Ok(None),
+        #[cfg(target_os = "linux")]
+        "uinput" => Ok(Some(GamepadBackend::Uinput)),
+        "memory" => Ok(Some(GamepadBackend::Memory)),
+        unknown_backend => Err(format!("Provided unknown gamepad backend {}, it can be uinput (Linux only) or memory", unknown_backend))
+    }
+}
+
+/// SystemInput of a category queue
+pub fn create_system_input(input: &InputConfig, category: &str) -> Result<Box<dyn SystemInput>, String> {
+    let system_input: Box<dyn SystemInput> = match input_backend_birth(input.category_backend(category))? {
+        InputBackend::Native => create_native_system_input()?,
+        InputBackend::Enigo => Box::new(EnigoSystemInput::new()),
+        InputBackend::Recording if input.recording_file.is_empty() => Box::new(RecordingSystemInput::logging()),
+        InputBackend::Recording => Box::new(RecordingSystemInput::with_file(&input.recording_file)?),
+        InputBackend::Remote => Box::new(RemoteSystemInput::new(&input.remote_address, &input.remote_token))
+    };
+    Ok(system_input)
+}
+
+/// Native SystemInput whatever the configured backend, to read the state of the PC. The error is a PC without access to its input
+pub fn create_native_system_input() -> Result<Box<dyn SystemInput>, String> {
+    #[cfg(target_os = "linux")]
+    let native_input = NativeSystemInput::new()?;
+    #[cfg(windows)]
+    let native_input = NativeSystemInput::new();
+    Ok(Box::new(native_input))
+}
+
+/// SystemInput reading the state of the PC a category plays on, None when its own backend reads it:
+/// enigo sends the input to this PC but can't read the mouse, the idle time or the focused window
+pub fn create_state_system_input(backend: InputBackend) -> Result<Option<Box<dyn SystemInput>>, String> {
+    match backend {
+        InputBackend::Enigo => create_native_system_input().map(Some),
+        _ => Ok(None)
+    }
+}
+
+/// Gamepad shared by every category, None when no gamepad backend is configured
+pub fn create_gamepad(gamepad: &GamepadConfig) -> Result<Option<SharedGamepad>, String> {
+    let gamepad_output: Box<dyn GamepadOutput + Send> = match gamepad_backend_birth(&gamepad.backend)? {
+        None => return Ok(None),
+        #[cfg(target_os = "linux")]
+        Some(GamepadBackend::Uinput) => Box::new(UinputGamepadOutput::new()?),
+        Some(GamepadBackend::Memory) => Box::new(MemoryGamepadOutput::logging())
+    };
+    Ok(Some(shared_gamepad(gamepad_output)))
+}
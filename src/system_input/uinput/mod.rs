@@ -0,0 +1 @@
+pub mod uinput_gamepad_output;
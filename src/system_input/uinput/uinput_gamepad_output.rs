@@ -0,0 +1,232 @@
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::mem::{size_of, zeroed};
+use std::os::unix::fs::OpenOptionsExt;
+use std::os::unix::io::AsRawFd;
+use std::os::raw::{c_int, c_ulong};
+use crate::system_input::gamepad_output::{GamepadOutput, GamepadButton, GamepadStick, GamepadTrigger};
+
+const UI_SET_EVBIT: c_ulong = 0x4004_5564;
+const UI_SET_KEYBIT: c_ulong = 0x4004_5565;
+const UI_SET_ABSBIT: c_ulong = 0x4004_5567;
+const UI_DEV_CREATE: c_ulong = 0x5501;
+const UI_DEV_DESTROY: c_ulong = 0x5502;
+
+const EV_SYN: u16 = 0x00;
+const EV_KEY: u16 = 0x01;
+const EV_ABS: u16 = 0x03;
+const SYN_REPORT: u16 = 0x00;
+const BUS_USB: u16 = 0x03;
+
+const ABS_X: u16 = 0x00;
+const ABS_Y: u16 = 0x01;
+const ABS_Z: u16 = 0x02;
+const ABS_RX: u16 = 0x03;
+const ABS_RY: u16 = 0x04;
+const ABS_RZ: u16 = 0x05;
+const ABS_HAT0X: u16 = 0x10;
+const ABS_HAT0Y: u16 = 0x11;
+
+const STICK_MAX: i32 = 32767;
+const TRIGGER_MAX: i32 = 255;
+const BUTTON_CODES: [u16; 11] = [0x130, 0x131, 0x133, 0x134, 0x136, 0x137, 0x13a, 0x13b, 0x13c, 0x13d, 0x13e];
+
+/// Device description of the legacy uinput setup, written before UI_DEV_CREATE
+#[repr(C)]
+struct UinputUserDev {
+    name: [u8; 80],
+    bustype: u16,
+    vendor: u16,
+    product: u16,
+    version: u16,
+    ff_effects_max: u32,
+    absmax: [i32; 64],
+    absmin: [i32; 64],
+    absfuzz: [i32; 64],
+    absflat: [i32; 64]
+}
+
+/// Directions of the d-pad held, each axis of the hat is the net value of its two directions
+#[derive(Default)]
+struct Dpad {
+    up: bool,
+    down: bool,
+    left: bool,
+    right: bool
+}
+
+impl Dpad {
+    /// Hat axis changed by the direction and its new value
+    fn set(&mut self, button: GamepadButton, is_press: bool) -> (u16, i32) {
+        match button {
+            GamepadButton::DpadUp => self.up = is_press,
+            GamepadButton::DpadDown => self.down = is_press,
+            GamepadButton::DpadLeft => self.left = is_press,
+            GamepadButton::DpadRight => self.right = is_press,
+            button => panic!("{:?} is a button, not a d-pad direction", button)
+        }
+        match button {
+            GamepadButton::DpadUp | GamepadButton::DpadDown => (ABS_HAT0Y, self.down as i32 - self.up as i32),
+            _ => (ABS_HAT0X, self.right as i32 - self.left as i32)
+        }
+    }
+}
+
+/// Gamepad created with uinput, it's seen by the games like a wired Xbox 360 controller.
+/// Writing /dev/uinput usually requires the user to be in the input group or an udev rule
+pub struct UinputGamepadOutput {
+    device: File,
+    dpad: Dpad
+}
+
+impl UinputGamepadOutput {
+    pub fn new() -> Result<UinputGamepadOutput, String> {
+        let device = OpenOptions::new().write(true).custom_flags(libc::O_NONBLOCK).open("/dev/uinput")
+            .map_err(|e| format!("Can't open /dev/uinput to create the gamepad, check its permissions: {}", e))?;
+        let fd = device.as_raw_fd();
+
+        let mut description: UinputUserDev = unsafe { zeroed() };
+        let name = b"Twitch Commands virtual gamepad";
+        description.name[..name.len()].copy_from_slice(name);
+        description.bustype = BUS_USB;
+        description.vendor = 0x045e;
+        description.product = 0x028e;
+        description.version = 1;
+        for (axis, min, max) in [(ABS_X, -STICK_MAX, STICK_MAX), (ABS_Y, -STICK_MAX, STICK_MAX), (ABS_RX, -STICK_MAX, STICK_MAX),
+                                 (ABS_RY, -STICK_MAX, STICK_MAX), (ABS_Z, 0, TRIGGER_MAX), (ABS_RZ, 0, TRIGGER_MAX),
+                                 (ABS_HAT0X, -1, 1), (ABS_HAT0Y, -1, 1)].iter() {
+            description.absmin[*axis as usize] = *min;
+            description.absmax[*axis as usize] = *max;
+            ioctl(fd, UI_SET_ABSBIT, *axis as c_int);
+        }
+        ioctl(fd, UI_SET_EVBIT, EV_KEY as c_int);
+        ioctl(fd, UI_SET_EVBIT, EV_ABS as c_int);
+        for code in BUTTON_CODES.iter() {
+            ioctl(fd, UI_SET_KEYBIT, *code as c_int);
+        }
+
+        let mut gamepad = UinputGamepadOutput { device, dpad: Dpad::default() };
+        gamepad.write_struct(&description);
+        ioctl(fd, UI_DEV_CREATE, 0);
+        Ok(gamepad)
+    }
+
+    fn write_struct<T>(&mut self, value: &T) {
+        let bytes = unsafe { std::slice::from_raw_parts(value as *const T as *const u8, size_of::<T>()) };
+        if let Err(e) = self.device.write_all(bytes) {
+            error!("uinput_gamepad_output::write_error::{}", e);
+        }
+    }
+
+    fn emit(&mut self, kind: u16, code: u16, value: i32) {
+        for (kind, code, value) in [(kind, code, value), (EV_SYN, SYN_REPORT, 0)].iter() {
+            let mut event: libc::input_event = unsafe { zeroed() };
+            event.type_ = *kind;
+            event.code = *code;
+            event.value = *value;
+            self.write_struct(&event);
+        }
+    }
+
+    fn button_event(&mut self, button: GamepadButton, is_press: bool) {
+        match button {
+            GamepadButton::DpadUp | GamepadButton::DpadDown | GamepadButton::DpadLeft | GamepadButton::DpadRight => {
+                let (axis, value) = self.dpad.set(button, is_press);
+                self.emit(EV_ABS, axis, value);
+            },
+            button => self.emit(EV_KEY, button_code(button), if is_press { 1 } else { 0 })
+        }
+    }
+}
+
+impl Drop for UinputGamepadOutput {
+    fn drop(&mut self) {
+        ioctl(self.device.as_raw_fd(), UI_DEV_DESTROY, 0);
+    }
+}
+
+impl GamepadOutput for UinputGamepadOutput {
+    fn button_down(&mut self, button: GamepadButton) {
+        self.button_event(button, true);
+    }
+
+    fn button_up(&mut self, button: GamepadButton) {
+        self.button_event(button, false);
+    }
+
+    fn move_stick(&mut self, stick: GamepadStick, x: f32, y: f32) {
+        let (x_axis, y_axis) = match stick {
+            GamepadStick::Left => (ABS_X, ABS_Y),
+            GamepadStick::Right => (ABS_RX, ABS_RY)
+        };
+        // Axes of evdev grow downwards
+        self.emit(EV_ABS, x_axis, (x.clamp(-1.0, 1.0) * STICK_MAX as f32).round() as i32);
+        self.emit(EV_ABS, y_axis, (-y.clamp(-1.0, 1.0) * STICK_MAX as f32).round() as i32);
+    }
+
+    fn pull_trigger(&mut self, trigger: GamepadTrigger, value: f32) {
+        let axis = match trigger {
+            GamepadTrigger::Left => ABS_Z,
+            GamepadTrigger::Right => ABS_RZ
+        };
+        self.emit(EV_ABS, axis, (value.clamp(0.0, 1.0) * TRIGGER_MAX as f32).round() as i32);
+    }
+}
+
+fn button_code(button: GamepadButton) -> u16 {
+    match button {
+        GamepadButton::A => 0x130,
+        GamepadButton::B => 0x131,
+        GamepadButton::X => 0x133,
+        GamepadButton::Y => 0x134,
+        GamepadButton::LeftBumper => 0x136,
+        GamepadButton::RightBumper => 0x137,
+        GamepadButton::Back => 0x13a,
+        GamepadButton::Start => 0x13b,
+        GamepadButton::Guide => 0x13c,
+        GamepadButton::LeftThumb => 0x13d,
+        GamepadButton::RightThumb => 0x13e,
+        dpad => panic!("D-pad {:?} is an axis, not a button", dpad)
+    }
+}
+
+fn ioctl(fd: c_int, request: c_ulong, value: c_int) {
+    if unsafe { libc::ioctl(fd, request as _, value) } < 0 {
+        error!("uinput_gamepad_output::ioctl_error::{:x}::{}", request, std::io::Error::last_os_error());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn every_button_has_a_declared_code() {
+        let buttons = [GamepadButton::A, GamepadButton::B, GamepadButton::X, GamepadButton::Y, GamepadButton::LeftBumper, GamepadButton::RightBumper,
+                       GamepadButton::Back, GamepadButton::Start, GamepadButton::Guide, GamepadButton::LeftThumb, GamepadButton::RightThumb];
+
+        assert!(buttons.iter().all(|button| BUTTON_CODES.contains(&button_code(*button))));
+    }
+
+    #[test]
+    fn opposite_directions_then_net_hat_value() {
+        let mut dpad = Dpad::default();
+
+        assert_eq!(dpad.set(GamepadButton::DpadLeft, true), (ABS_HAT0X, -1));
+        assert_eq!(dpad.set(GamepadButton::DpadRight, true), (ABS_HAT0X, 0));
+        assert_eq!(dpad.set(GamepadButton::DpadLeft, false), (ABS_HAT0X, 1));
+        assert_eq!(dpad.set(GamepadButton::DpadUp, true), (ABS_HAT0Y, -1));
+        assert_eq!(dpad.set(GamepadButton::DpadRight, false), (ABS_HAT0X, 0));
+    }
+
+    #[test]
+    #[ignore]
+    fn gamepad_created_on_uinput() {
+        let mut gamepad = UinputGamepadOutput::new().unwrap();
+
+        gamepad.button_down(GamepadButton::A);
+        gamepad.move_stick(GamepadStick::Left, 0.5, -1.0);
+        gamepad.pull_trigger(GamepadTrigger::Right, 1.0);
+        gamepad.button_up(GamepadButton::A);
+    }
+}
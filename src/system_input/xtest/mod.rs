@@ -0,0 +1 @@
+pub mod xtest_system_input;
@@ -0,0 +1,332 @@
+use crate::system_input::system_input::{SystemInput, MouseButton, FocusedWindow, mark_injected_input, physical_idle_time};
+use tokio::time::{Duration, sleep, Sleep};
+use std::ptr::{null, null_mut};
+use std::ffi::CString;
+use std::fs;
+use std::os::raw::{c_char, c_int, c_uchar, c_uint, c_ulong};
+use x11::xlib::*;
+use x11::xss::*;
+use x11::xtest::*;
+use crate::{s};
+
+/// Input for X11 sessions, events are injected with the XTest extension
+pub struct XTestSystemInput {
+    display: *mut Display
+}
+
+impl XTestSystemInput {
+    pub fn new() -> Result<XTestSystemInput, String> {
+        let display = unsafe { XOpenDisplay(null()) };
+        if display.is_null() {
+            return Err(s!("Can't open X display, check the DISPLAY environment variable"));
+        }
+        Ok(XTestSystemInput { display })
+    }
+
+    fn query_pointer(&self) -> Option<(i32, i32, c_uint)> {
+        let (mut root, mut child) = (0, 0);
+        let (mut root_x, mut root_y, mut window_x, mut window_y) = (0, 0, 0, 0);
+        let mut mask = 0;
+        let on_screen = unsafe {
+            XQueryPointer(self.display, XDefaultRootWindow(self.display), &mut root, &mut child,
+                          &mut root_x, &mut root_y, &mut window_x, &mut window_y, &mut mask)
+        };
+        if on_screen != 0 { Some((root_x, root_y, mask)) } else { None }
+    }
+
+    /// Reads a window property with an Xlib format of 8 or 32 bits per item, 32 bits items are stored as c_ulong
+    fn window_property<T>(&self, window: Window, name: &str, read: impl FnOnce(c_int, *const c_uchar, usize) -> T) -> Option<T> {
+        let name = CString::new(name).unwrap();
+        let (mut actual_type, mut actual_format, mut items, mut bytes_after) = (0, 0, 0, 0);
+        let mut data: *mut c_uchar = null_mut();
+        unsafe {
+            let property = XInternAtom(self.display, name.as_ptr(), True);
+            if property == 0 {
+                return None;
+            }
+            let status = XGetWindowProperty(self.display, window, property, 0, 1024, False, AnyPropertyType as Atom,
+                                            &mut actual_type, &mut actual_format, &mut items, &mut bytes_after, &mut data);
+            if status != Success as c_int || data.is_null() {
+                return None;
+            }
+            let value = if items > 0 { Some(read(actual_format, data, items as usize)) } else { None };
+            XFree(data as *mut _);
+            value
+        }
+    }
+
+    fn window_property_number(&self, window: Window, name: &str) -> Option<c_ulong> {
+        self.window_property(window, name, |format, data, _| if format == 32 { Some(unsafe { *(data as *const c_ulong) }) } else { None })
+            .flatten()
+    }
+
+    fn window_property_text(&self, window: Window, name: &str) -> Option<String> {
+        self.window_property(window, name, |format, data, items| if format == 8 {
+            Some(String::from_utf8_lossy(unsafe { std::slice::from_raw_parts(data, items) }).into_owned())
+        } else {
+            None
+        }).flatten()
+    }
+
+    fn button_event(&mut self, button: c_uint, is_press: bool) {
+        unsafe {
+            XTestFakeButtonEvent(self.display, button, is_press as c_int, CurrentTime);
+            XFlush(self.display);
+        }
+        mark_injected_input();
+    }
+
+    fn key_event(&mut self, keycode: KeyCode, is_press: bool) {
+        unsafe {
+            XTestFakeKeyEvent(self.display, keycode as c_uint, is_press as c_int, CurrentTime);
+            XFlush(self.display);
+        }
+        mark_injected_input();
+    }
+
+    fn raw_key_event(&mut self, raw: u16, is_press: bool) {
+        match keysym_from_key_code(raw).map(|keysym| unsafe { XKeysymToKeycode(self.display, keysym) }) {
+            Some(keycode) if keycode != 0 => self.key_event(keycode, is_press),
+            _ => warn!("Key code {} has no key in the X keyboard mapping", raw)
+        }
+    }
+
+    /// Types a keysym that is not in the keyboard mapping binding it temporarily to an unused keycode
+    fn type_unmapped_keysym(&mut self, keysym: KeySym) {
+        let (mut min_keycode, mut max_keycode, mut keysyms_per_keycode) = (0, 0, 0);
+        unsafe {
+            XDisplayKeycodes(self.display, &mut min_keycode, &mut max_keycode);
+            let mapping = XGetKeyboardMapping(self.display, min_keycode as KeyCode, max_keycode - min_keycode + 1, &mut keysyms_per_keycode);
+            let per_keycode = keysyms_per_keycode as usize;
+            let spare_keycode = (min_keycode..=max_keycode).rev().find(|keycode| {
+                let first = (*keycode - min_keycode) as usize * per_keycode;
+                (first..first + per_keycode).all(|index| *mapping.add(index) == 0)
+            });
+            XFree(mapping as *mut _);
+
+            match spare_keycode {
+                Some(keycode) => {
+                    let mut keysyms = [keysym, keysym];
+                    XChangeKeyboardMapping(self.display, keycode, 2, keysyms.as_mut_ptr(), 1);
+                    XSync(self.display, False);
+                    self.key_event(keycode as KeyCode, true);
+                    self.key_event(keycode as KeyCode, false);
+                    XSync(self.display, False);
+                    let mut no_keysyms = [0, 0];
+                    XChangeKeyboardMapping(self.display, keycode, 2, no_keysyms.as_mut_ptr(), 1);
+                    XFlush(self.display);
+                },
+                None => warn!("No free keycode to type keysym {:x}", keysym)
+            }
+        }
+    }
+}
+
+impl Drop for XTestSystemInput {
+    fn drop(&mut self) {
+        unsafe { XCloseDisplay(self.display) };
+    }
+}
+
+impl SystemInput for XTestSystemInput {
+    fn is_mouse_left_down(&self) -> bool {
+        matches!(self.query_pointer(), Some((_, _, mask)) if mask & Button1Mask != 0)
+    }
+
+    fn idle_time(&self) -> Option<Duration> {
+        unsafe {
+            let info = XScreenSaverAllocInfo();
+            if info.is_null() {
+                return None;
+            }
+            let status = XScreenSaverQueryInfo(self.display, XDefaultRootWindow(self.display), info);
+            let idle_ms = (*info).idle;
+            XFree(info as *mut _);
+            if status == 0 { None } else { physical_idle_time(Duration::from_millis(idle_ms as u64)) }
+        }
+    }
+
+    fn is_key_down(&self, raw: u16) -> bool {
+        let keycode = match keysym_from_key_code(raw).map(|keysym| unsafe { XKeysymToKeycode(self.display, keysym) }) {
+            Some(keycode) if keycode != 0 => keycode as usize,
+            _ => return false
+        };
+        let mut keys: [c_char; 32] = [0; 32];
+        unsafe { XQueryKeymap(self.display, keys.as_mut_ptr()) };
+        keys[keycode / 8] as u8 & (1 << (keycode % 8)) != 0
+    }
+
+    /// Active window as published by the window manager, the process name comes from /proc
+    fn focused_window(&self) -> Option<FocusedWindow> {
+        let root = unsafe { XDefaultRootWindow(self.display) };
+        let window = match self.window_property_number(root, "_NET_ACTIVE_WINDOW") {
+            Some(window) if window != 0 => window,
+            _ => return Some(FocusedWindow::default())
+        };
+
+        Some(FocusedWindow {
+            title: self.window_property_text(window, "_NET_WM_NAME")
+                .or_else(|| self.window_property_text(window, "WM_NAME"))
+                .unwrap_or_default(),
+            process: self.window_property_number(window, "_NET_WM_PID")
+                .and_then(|pid| fs::read_to_string(format!("/proc/{}/comm", pid)).ok())
+                .map(|name| name.trim().to_string())
+                .unwrap_or_default()
+        })
+    }
+
+    fn mouse_position(&self) -> Option<(i32, i32)> {
+        self.query_pointer().map(|(x, y, _)| (x, y))
+    }
+
+    fn move_mouse_of(&mut self, x: i32, y: i32) {
+        unsafe {
+            XTestFakeRelativeMotionEvent(self.display, -1, x, y, CurrentTime);
+            XFlush(self.display);
+        }
+        mark_injected_input();
+    }
+
+    fn move_mouse_to(&mut self, x: i32, y: i32) {
+        unsafe {
+            XTestFakeMotionEvent(self.display, -1, x, y, CurrentTime);
+            XFlush(self.display);
+        }
+        mark_injected_input();
+    }
+
+    fn move_mouse_to_normalized(&mut self, x: f32, y: f32) {
+        let (width, height) = unsafe {
+            let screen = XDefaultScreen(self.display);
+            (XDisplayWidth(self.display, screen), XDisplayHeight(self.display, screen))
+        };
+        self.move_mouse_to(
+            (x.clamp(0.0, 1.0) * (width - 1) as f32).round() as i32,
+            (y.clamp(0.0, 1.0) * (height - 1) as f32).round() as i32
+        );
+    }
+
+    fn mouse_down(&mut self, button: MouseButton) {
+        self.button_event(x_button(button), true);
+    }
+
+    fn mouse_up(&mut self, button: MouseButton) {
+        self.button_event(x_button(button), false);
+    }
+
+    fn scroll(&mut self, x: i32, y: i32) {
+        let vertical_button = if y > 0 { 4 } else { 5 };
+        let horizontal_button = if x > 0 { 7 } else { 6 };
+        for (button, notches) in [(vertical_button, y.abs()), (horizontal_button, x.abs())].iter() {
+            for _ in 0..*notches {
+                self.button_event(*button, true);
+                self.button_event(*button, false);
+            }
+        }
+    }
+
+    fn delay_for(&mut self, ms: u64) -> Sleep {
+        sleep(Duration::from_millis(ms))
+    }
+
+    fn key_down(&mut self, raw: u16) {
+        self.raw_key_event(raw, true);
+    }
+
+    fn key_up(&mut self, raw: u16) {
+        self.raw_key_event(raw, false);
+    }
+
+    fn type_char(&mut self, character: char) {
+        let keysym = match character {
+            '\n' => 0xff0d,
+            '\t' => 0xff09,
+            latin1 if (latin1 as u32) < 0x100 => latin1 as KeySym,
+            unicode => 0x0100_0000 | unicode as KeySym
+        };
+        let keycode = unsafe { XKeysymToKeycode(self.display, keysym) };
+        let (unshifted, shifted) = unsafe { (XKeycodeToKeysym(self.display, keycode, 0), XKeycodeToKeysym(self.display, keycode, 1)) };
+        if keycode == 0 || (unshifted != keysym && shifted != keysym) {
+            return self.type_unmapped_keysym(keysym);
+        }
+
+        let needs_shift = unshifted != keysym;
+        let shift_keycode = unsafe { XKeysymToKeycode(self.display, XK_SHIFT_L) };
+        if needs_shift {
+            self.key_event(shift_keycode, true);
+        }
+        self.key_event(keycode, true);
+        self.key_event(keycode, false);
+        if needs_shift {
+            self.key_event(shift_keycode, false);
+        }
+    }
+}
+
+const XK_SHIFT_L: KeySym = 0xffe1;
+
+fn x_button(button: MouseButton) -> c_uint {
+    match button {
+        MouseButton::Left => 1,
+        MouseButton::Middle => 2,
+        MouseButton::Right => 3,
+        MouseButton::Extra1 => 8,
+        MouseButton::Extra2 => 9
+    }
+}
+
+/// X keysym of the key codes used in the actions DSL (Windows virtual-key codes)
+fn keysym_from_key_code(raw: u16) -> Option<KeySym> {
+    let keysym = match raw {
+        8 => 0xff08, 9 => 0xff09, 13 => 0xff0d, 16 | 160 => XK_SHIFT_L, 161 => 0xffe2, 17 | 162 => 0xffe3,
+        163 => 0xffe4, 18 | 164 => 0xffe9, 165 => 0xffea, 19 => 0xff13, 20 => 0xffe5, 27 => 0xff1b, 32 => 0x20,
+        33 => 0xff55, 34 => 0xff56, 35 => 0xff57, 36 => 0xff50, 37 => 0xff51, 38 => 0xff52, 39 => 0xff53,
+        40 => 0xff54, 44 => 0xff61, 45 => 0xff63, 46 => 0xffff, 91 => 0xffeb, 92 => 0xffec, 144 => 0xff7f,
+        145 => 0xff14, 106 => 0xffaa, 107 => 0xffab, 109 => 0xffad, 110 => 0xffae, 111 => 0xffaf,
+        186 => 0x3b, 187 => 0x3d, 188 => 0x2c, 189 => 0x2d, 190 => 0x2e, 191 => 0x2f, 192 => 0x60,
+        219 => 0x5b, 220 => 0x5c, 221 => 0x5d, 222 => 0x27,
+        digit @ 48..=57 => digit as KeySym,
+        letter @ 65..=90 => (letter + 32) as KeySym,
+        numpad @ 96..=105 => 0xffb0 + (numpad - 96) as KeySym,
+        function @ 112..=135 => 0xffbe + (function - 112) as KeySym,
+        _ => return None
+    };
+    Some(keysym)
+}
+
+/// These tests need an X server, run them with `xvfb-run cargo test -- --ignored`
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    #[ignore]
+    fn move_mouse_to_then_mouse_position_is_the_same() {
+        let mut input = XTestSystemInput::new().unwrap();
+
+        input.move_mouse_to(10, 20);
+        input.move_mouse_of(5, -5);
+
+        assert_eq!(input.mouse_position(), Some((15, 15)));
+    }
+
+    #[test]
+    #[ignore]
+    fn mouse_left_down_then_is_mouse_left_down_until_up() {
+        let mut input = XTestSystemInput::new().unwrap();
+
+        input.mouse_down(MouseButton::Left);
+        assert!(input.is_mouse_left_down());
+        input.mouse_up(MouseButton::Left);
+        assert!(!input.is_mouse_left_down());
+    }
+
+    #[test]
+    fn key_codes_of_letters_digits_and_function_keys_are_x_keysyms() {
+        assert_eq!(keysym_from_key_code(65), Some(0x61));
+        assert_eq!(keysym_from_key_code(48), Some(0x30));
+        assert_eq!(keysym_from_key_code(116), Some(0xffc2));
+        assert_eq!(keysym_from_key_code(38), Some(0xff52));
+        assert_eq!(keysym_from_key_code(0), None);
+    }
+}
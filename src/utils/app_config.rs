@@ -1,6 +1,10 @@
 use std::fs;
+use std::collections::HashMap;
 use serde::{Deserialize};
 use serde::de::DeserializeOwned;
+use crate::stream_interface::twitch::stream_category::DEFAULT_HELIX_BASE_URL;
+use crate::utils::config_validation::ConfigProblem;
+use crate::{s};
 
 #[derive(Deserialize)]
 #[derive(Debug)]
@@ -11,7 +15,254 @@ pub struct AppConfig {
     #[serde(default)]
     pub terminal_log_level: String,
     pub twitch_stream: TwitchStreamConfig,
-    pub mapping: Mapping
+    #[serde(default)]
+    pub mapping: Mapping,
+    #[serde(default)]
+    pub profiles: HashMap<String, Mapping>,
+    #[serde(default)]
+    pub profile: ProfileConfig,
+    #[serde(default)]
+    pub input: InputConfig,
+    #[serde(default)]
+    pub pause: PauseConfig,
+    #[serde(default)]
+    pub target_window: TargetWindowConfig,
+    #[serde(default)]
+    pub gamepad: GamepadConfig,
+    #[serde(default)]
+    pub queue: QueueConfig,
+    #[serde(default)]
+    pub control: ControlConfig,
+    #[serde(default)]
+    pub shutdown: ShutdownConfig,
+    #[serde(default)]
+    pub reload: ReloadConfig
+}
+
+#[derive(Deserialize)]
+#[derive(Debug)]
+#[derive(Default)]
+pub struct InputConfig {
+    #[serde(default)]
+    pub backend: String,
+    #[serde(default)]
+    pub categories: HashMap<String, String>,
+    #[serde(default)]
+    pub recording_file: String,
+    #[serde(default)]
+    pub remote_address: String,
+    #[serde(default)]
+    pub remote_token: String
+}
+
+impl InputConfig {
+    /// Input backend of a category queue, the category one if configured or the general one
+    pub fn category_backend(&self, category: &str) -> &str {
+        self.categories.get(category).unwrap_or(&self.backend)
+    }
+
+    /// Every category records the input instead of sending it to the PC
+    pub fn dry_run(&mut self) {
+        self.backend = s!("recording");
+        self.categories.clear();
+    }
+}
+
+#[derive(Deserialize)]
+#[derive(Debug)]
+#[derive(Default)]
+pub struct GamepadConfig {
+    #[serde(default)]
+    pub backend: String
+}
+
+impl GamepadConfig {
+    /// The configured gamepad keeps its input in memory instead of creating a device
+    pub fn dry_run(&mut self) {
+        if !self.backend.is_empty() {
+            self.backend = s!("memory");
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[derive(Debug)]
+pub struct PauseConfig {
+    #[serde(default)]
+    pub toggle_hotkey: String,
+    #[serde(default = "default_pause_conditions")]
+    pub conditions: Vec<String>,
+    #[serde(default)]
+    pub categories: HashMap<String, Vec<String>>
+}
+
+impl Default for PauseConfig {
+    fn default() -> Self {
+        PauseConfig { toggle_hotkey: s!(""), conditions: default_pause_conditions(), categories: HashMap::new() }
+    }
+}
+
+impl PauseConfig {
+    /// Pause conditions of a category queue, the category ones if configured or the general ones
+    pub fn category_conditions(&self, category: &str) -> &[String] {
+        self.categories.get(category).unwrap_or(&self.conditions)
+    }
+}
+
+fn default_pause_conditions() -> Vec<String> {
+    vec![s!("mouse_left_down")]
+}
+
+#[derive(Deserialize)]
+#[derive(Debug)]
+#[derive(Default)]
+pub struct TargetWindowConfig {
+    #[serde(flatten)]
+    pub target: WindowTargetConfig,
+    #[serde(default)]
+    pub categories: HashMap<String, WindowTargetConfig>
+}
+
+impl TargetWindowConfig {
+    /// Target window of a category queue, the category one if configured or the general one
+    pub fn category_target(&self, category: &str) -> &WindowTargetConfig {
+        self.categories.get(category).unwrap_or(&self.target)
+    }
+}
+
+#[derive(Deserialize)]
+#[derive(Debug)]
+#[derive(Default)]
+pub struct WindowTargetConfig {
+    #[serde(default)]
+    pub title: String,
+    #[serde(default)]
+    pub process: String,
+    #[serde(default)]
+    pub policy: String
+}
+
+#[derive(Deserialize)]
+#[derive(Debug)]
+#[derive(Default)]
+pub struct QueueConfig {
+    #[serde(flatten)]
+    pub general: CategoryQueueConfig,
+    #[serde(default)]
+    pub categories: HashMap<String, CategoryQueueConfig>
+}
+
+impl QueueConfig {
+    /// Configuration of a category queue, the category one if configured or the general one
+    pub fn category_queue(&self, category: &str) -> &CategoryQueueConfig {
+        self.categories.get(category).unwrap_or(&self.general)
+    }
+}
+
+#[derive(Deserialize)]
+#[derive(Debug)]
+#[derive(Default)]
+pub struct CategoryQueueConfig {
+    pub max_length: Option<usize>,
+    #[serde(default)]
+    pub overflow: String,
+    pub ttl_seconds: Option<u64>,
+    #[serde(default)]
+    pub fairness: String,
+    pub max_per_user: Option<usize>,
+    pub subscriber_weight: Option<u32>,
+    pub bits_weight: Option<u32>,
+    #[serde(default)]
+    pub exclusive: bool,
+    #[serde(default)]
+    pub locks: Vec<String>
+}
+
+#[derive(Deserialize)]
+#[derive(Debug)]
+pub struct ControlConfig {
+    #[serde(default = "default_control_commands")]
+    pub commands: Vec<ControlCommandConfig>
+}
+
+impl Default for ControlConfig {
+    fn default() -> Self {
+        ControlConfig { commands: default_control_commands() }
+    }
+}
+
+#[derive(Deserialize)]
+#[derive(Debug)]
+#[derive(Clone)]
+pub struct ControlCommandConfig {
+    pub trigger: String,
+    pub effect: String,
+    #[serde(default)]
+    pub roles: Vec<String>,
+    #[serde(default)]
+    pub category: String,
+    #[serde(default)]
+    pub profile: String
+}
+
+#[derive(Deserialize)]
+#[derive(Debug)]
+#[derive(Default)]
+pub struct ShutdownConfig {
+    #[serde(default)]
+    pub mode: String,
+    pub timeout_seconds: Option<u64>
+}
+
+#[derive(Deserialize)]
+#[derive(Debug)]
+pub struct ReloadConfig {
+    #[serde(default = "default_watch")]
+    pub watch: bool,
+    pub watch_interval_ms: Option<u64>
+}
+
+impl Default for ReloadConfig {
+    fn default() -> Self {
+        ReloadConfig { watch: default_watch(), watch_interval_ms: None }
+    }
+}
+
+fn default_watch() -> bool {
+    true
+}
+
+#[derive(Deserialize)]
+#[derive(Debug)]
+pub struct ProfileConfig {
+    /// Profile used at startup, empty for the common mapping only
+    #[serde(default)]
+    pub default: String,
+    /// Address of the local control API, empty to disable it
+    #[serde(default)]
+    pub api_address: String,
+    #[serde(default = "default_helix_base_url")]
+    pub helix_base_url: String,
+    /// Profile to switch to for each stream category (game name), empty to disable the automatic switch
+    #[serde(default)]
+    pub stream_categories: HashMap<String, String>,
+    pub check_interval_seconds: Option<u64>
+}
+
+impl Default for ProfileConfig {
+    fn default() -> Self {
+        ProfileConfig { default: s!(""), api_address: s!(""), helix_base_url: default_helix_base_url(), stream_categories: HashMap::new(), check_interval_seconds: None }
+    }
+}
+
+fn default_helix_base_url() -> String {
+    s!(DEFAULT_HELIX_BASE_URL)
+}
+
+fn default_control_commands() -> Vec<ControlCommandConfig> {
+    [("!stop", "stop"), ("!release", "release"), ("!pause", "pause"), ("!resume", "resume"), ("!clear", "clear"), ("!skip", "skip"), ("!accepting", "toggle_accepting")].iter()
+        .map(|(trigger, effect)| ControlCommandConfig { trigger: s!(trigger), effect: s!(effect), roles: vec![s!("mod")], category: s!(""), profile: s!("") })
+        .collect()
 }
 
 #[derive(Deserialize)]
@@ -25,12 +276,16 @@ pub struct TwitchStreamConfig {
 
 #[derive(Deserialize)]
 #[derive(Debug)]
+#[derive(Default)]
+#[derive(Clone)]
 pub struct Mapping {
+    #[serde(default)]
     pub config: Vec<MappingConfig>
 }
 
 #[derive(Deserialize)]
 #[derive(Debug)]
+#[derive(Clone)]
 pub struct MappingConfig {
     pub source: String,
     pub id: String,
@@ -41,27 +296,53 @@ pub struct MappingConfig {
     pub category: String,
     #[serde(default)]
     pub comparison: String,
-    pub limit: Option<u16>
+    pub limit: Option<u16>,
+    pub text: Option<TextConfig>,
+    #[serde(default)]
+    pub priority: i32,
+    #[serde(default)]
+    pub preempt: String
 }
 
-pub fn app_config() -> AppConfig {
-    let filename = "config.toml";
-    let current_version = "1.0";
+#[derive(Deserialize)]
+#[derive(Debug)]
+#[derive(Clone)]
+pub struct TextConfig {
+    #[serde(default)]
+    pub allowed_characters: String,
+    pub max_length: Option<usize>,
+    #[serde(default)]
+    pub blocked_words: Vec<String>
+}
 
+pub const CONFIG_FILE: &str = "config.toml";
+const CURRENT_VERSION: &str = "1.0";
+
+/// Configuration read from the file, without checking the values
+pub fn read_app_config(filename: &str) -> Result<AppConfig, Vec<ConfigProblem>> {
     from_toml_file::<AppConfig>(filename)
         .and_then(|config|
-            if config.version != current_version {
-                Err(format!("Configuration layout changed, update the config file and file version to {}", current_version))
+            if config.version != CURRENT_VERSION {
+                Err(ConfigProblem::new("version", &format!("configuration layout changed, update the config file and file version to {}", CURRENT_VERSION)))
             } else {
                 Ok(config)
             }
         )
-        .expect("")
+        .map_err(|problem| vec![problem])
 }
 
-fn from_toml_file<T: DeserializeOwned>(filename: &str) -> Result<T, String> {
+fn from_toml_file<T: DeserializeOwned>(filename: &str) -> Result<T, ConfigProblem> {
     fs::read_to_string(filename)
-        .map_err(|_| format!("file `{}` is missing", filename))
-        .and_then(|file_content| toml::from_str::<T>(file_content.as_str())
-            .map_err(|_| format!("`{}` content is incorrect", filename)))
+        .map_err(|_| ConfigProblem::new("file", "missing"))
+        .and_then(|file_content| toml::from_str::<T>(file_content.as_str()).map_err(|e| toml_problem(&e)))
+}
+
+/// The position of the TOML error becomes the location, it's 0 based in the error
+fn toml_problem(error: &toml::de::Error) -> ConfigProblem {
+    let message = error.to_string();
+    let message = message.split(" at line ").next().unwrap_or_default();
+    match error.line_col() {
+        Some((line, column)) => ConfigProblem::new(&format!("line {}, column {}", line + 1, column + 1), message),
+        None => ConfigProblem::new("file", message)
+    }
 }
@@ -0,0 +1,183 @@
+use crate::utils::app_config::{AppConfig, Mapping, CONFIG_FILE};
+use crate::{s};
+
+pub const USAGE: &str = "Usage: twitch_commands [command] [options]
+
+Commands:
+  run                  Connect to the stream and send the actions to the PC, the default
+  validate             Check the configuration and exit
+  dry-run              Connect to the stream and log the actions instead of sending them
+  replay <file>        Send again the chat events recorded in the file, logging the actions instead of sending them
+  record <file>        Run, appending the chat events received to the file
+  list-mappings        Print the mappings of the profile and exit
+  help                 Print this help
+
+Options:
+  --config <path>               Configuration file, config.toml by default
+  --profile <name>              Profile used at startup instead of the configured one
+  --terminal-log-level <level>  Log level in terminal: off, error, warning, info, debug or trace
+  --file-log-level <level>      Log level in file: off, error, warning, info, debug or trace";
+
+const LOG_LEVELS: [&str; 6] = ["off", "error", "warning", "info", "debug", "trace"];
+
+#[derive(Debug)]
+#[derive(Clone)]
+#[derive(PartialEq)]
+pub enum Command {
+    Run,
+    Validate,
+    DryRun,
+    Replay(String),
+    Record(String),
+    ListMappings,
+    Help
+}
+
+#[derive(Debug)]
+#[derive(PartialEq)]
+pub struct Cli {
+    pub command: Command,
+    pub config_file: String,
+    pub profile: Option<String>,
+    pub terminal_log_level: Option<String>,
+    pub file_log_level: Option<String>
+}
+
+impl Cli {
+    /// The options given on the command line win over the configuration file
+    pub fn override_config(&self, config: &mut AppConfig) {
+        if let Some(ref profile) = self.profile {
+            config.profile.default = profile.clone();
+        }
+        if let Some(ref level) = self.terminal_log_level {
+            config.terminal_log_level = level.clone();
+        }
+        if let Some(ref level) = self.file_log_level {
+            config.file_log_level = level.clone();
+        }
+    }
+
+    /// The actions are logged instead of being sent to the PC
+    pub fn is_dry_run(&self) -> bool {
+        matches!(self.command, Command::DryRun | Command::Replay(_))
+    }
+}
+
+/// Command line arguments, the program name excluded. Without command it runs, `--dry-run` is kept for the dry-run command
+pub fn cli_birth(args: &[String]) -> Result<Cli, String> {
+    let mut command = None;
+    let mut cli = Cli { command: Command::Run, config_file: s!(CONFIG_FILE), profile: None, terminal_log_level: None, file_log_level: None };
+    let mut args = args.iter();
+
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().cloned().ok_or_else(|| format!("Missing value of {}", arg));
+        let parsed = match arg.as_str() {
+            "--config" => { cli.config_file = value()?; None },
+            "--profile" => { cli.profile = Some(value()?); None },
+            "--terminal-log-level" => { cli.terminal_log_level = Some(log_level(value()?)?); None },
+            "--file-log-level" => { cli.file_log_level = Some(log_level(value()?)?); None },
+            "--dry-run" => Some(Command::DryRun),
+            "--help" | "-h" | "help" => Some(Command::Help),
+            "run" => Some(Command::Run),
+            "validate" => Some(Command::Validate),
+            "dry-run" => Some(Command::DryRun),
+            "replay" => Some(Command::Replay(value().map_err(|_| s!("Missing events file of replay"))?)),
+            "record" => Some(Command::Record(value().map_err(|_| s!("Missing events file of record"))?)),
+            "list-mappings" => Some(Command::ListMappings),
+            unknown if unknown.starts_with('-') => return Err(format!("Unknown option {}", unknown)),
+            unknown => return Err(format!("Unknown command {}", unknown))
+        };
+        if let Some(parsed) = parsed {
+            if command.is_some() {
+                return Err(format!("Only one command can be given, found {}", arg));
+            }
+            command = Some(parsed);
+        }
+    }
+
+    cli.command = command.unwrap_or(Command::Run);
+    Ok(cli)
+}
+
+fn log_level(level: String) -> Result<String, String> {
+    if LOG_LEVELS.contains(&level.as_str()) {
+        Ok(level)
+    } else {
+        Err(format!("Unknown log level {}, it can be {}", level, LOG_LEVELS.join(", ")))
+    }
+}
+
+/// Mappings as printed by list-mappings, one per line
+pub fn mappings_listing(profile: &str, mapping: &Mapping) -> String {
+    let title = if profile.is_empty() { s!("Mappings without profile") } else { format!("Mappings of profile {}", profile) };
+    let lines = mapping.config.iter().map(|config| {
+        let mut line = format!("  {} `{}`", config.source, config.id);
+        if !config.name.is_empty() {
+            line.push_str(&format!(" ({})", config.name));
+        }
+        if !config.comparison.is_empty() {
+            line.push_str(&format!(" [{}]", config.comparison));
+        }
+        line.push_str(&format!(" -> {}", config.actions.join(" ")));
+        if !config.category.is_empty() {
+            line.push_str(&format!(" in {}", config.category));
+        }
+        if let Some(limit) = config.limit {
+            line.push_str(&format!(", limit {}", limit));
+        }
+        line
+    }).collect::<Vec<String>>();
+    format!("{}, {} found:\n{}", title, lines.len(), lines.join("\n"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::app_config::MappingConfig;
+
+    fn args(line: &str) -> Vec<String> {
+        line.split_whitespace().map(|arg| s!(arg)).collect()
+    }
+
+    #[test]
+    fn no_arguments_then_run_with_default_config() {
+        assert_eq!(cli_birth(&[]), Ok(Cli { command: Command::Run, config_file: s!("config.toml"), profile: None, terminal_log_level: None, file_log_level: None }));
+        assert_eq!(cli_birth(&args("--dry-run")).unwrap().command, Command::DryRun);
+    }
+
+    #[test]
+    fn command_and_options_in_any_order() {
+        let cli = cli_birth(&args("--config games/racing.toml replay events.jsonl --profile racing --terminal-log-level debug")).unwrap();
+
+        assert_eq!(cli, Cli {
+            command: Command::Replay(s!("events.jsonl")),
+            config_file: s!("games/racing.toml"),
+            profile: Some(s!("racing")),
+            terminal_log_level: Some(s!("debug")),
+            file_log_level: None
+        });
+        assert!(cli.is_dry_run());
+    }
+
+    #[test]
+    fn wrong_arguments_then_error() {
+        assert_eq!(cli_birth(&args("jump")), Err(s!("Unknown command jump")));
+        assert_eq!(cli_birth(&args("run --verbose")), Err(s!("Unknown option --verbose")));
+        assert_eq!(cli_birth(&args("record")), Err(s!("Missing events file of record")));
+        assert_eq!(cli_birth(&args("--config")), Err(s!("Missing value of --config")));
+        assert_eq!(cli_birth(&args("validate run")), Err(s!("Only one command can be given, found run")));
+        assert!(cli_birth(&args("--file-log-level loud")).err().unwrap().starts_with("Unknown log level loud"));
+    }
+
+    #[test]
+    fn mappings_listed_one_per_line() {
+        let mapping = Mapping {
+            config: vec![MappingConfig {
+                source: s!("action"), id: s!("10-100"), actions: vec![s!("kd32"), s!("w100")], name: s!("bits"), category: s!("movement"),
+                comparison: s!("range"), limit: Some(3), text: None, priority: 0, preempt: s!("")
+            }]
+        };
+
+        assert_eq!(mappings_listing("racing", &mapping), "Mappings of profile racing, 1 found:\n  action `10-100` (bits) [range] -> kd32 w100 in movement, limit 3");
+    }
+}
@@ -0,0 +1,285 @@
+use std::collections::BTreeMap;
+use std::fmt::{Display, Formatter, Result as FmtResult};
+use crate::actions::keymap::key_code;
+use crate::actions::locks::{category_locks_birth, SharedLocks};
+use crate::actions::pause::pause_condition_birth;
+use crate::actions::queue::UNCATEGORIZED_CHANNEL_NAME;
+use crate::actions::queue_policy::queue_policy_birth;
+use crate::actions::window_guard::window_guard_birth;
+use crate::control::control_commands::control_command_birth;
+use crate::control::shutdown::shutdown_policy_birth;
+use crate::event_to_action::configurable_event_to_action::configurable_event_to_action::{action_birth, comparison_range_builder, scheduling_birth};
+use crate::system_input::enigo::enigo_system_input::SUPPORTS_NORMALIZED_MOVES;
+use crate::system_input::system_input_backend::{gamepad_backend_birth, input_backend_birth, InputBackend};
+use crate::utils::app_config::{read_app_config, AppConfig, InputConfig, MappingConfig};
+use crate::{s};
+
+/// Exit code of the process when the configuration is wrong
+pub const INVALID_CONFIG_EXIT_CODE: i32 = 2;
+
+const ACTION_NAMES: [&str; 2] = ["reward_redeem", "bits"];
+
+/// What is wrong in the configuration and where: a line and column, a mapping index or a setting
+#[derive(Debug)]
+#[derive(PartialEq)]
+pub struct ConfigProblem {
+    pub location: String,
+    pub message: String
+}
+
+impl ConfigProblem {
+    pub fn new(location: &str, message: &str) -> Self {
+        ConfigProblem { location: s!(location), message: s!(message) }
+    }
+}
+
+impl Display for ConfigProblem {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        write!(f, "{}: {}", self.location, self.message)
+    }
+}
+
+/// Every problem of the file, one per line
+pub fn problems_report(filename: &str, problems: &[ConfigProblem]) -> String {
+    let lines = problems.iter().map(|problem| format!("  - {}", problem)).collect::<Vec<_>>();
+    format!("`{}` content is incorrect, {} problem(s) found:\n{}", filename, problems.len(), lines.join("\n"))
+}
+
+/// Configuration read and checked, the error is the report of every problem found
+pub fn validated_app_config(filename: &str) -> Result<AppConfig, String> {
+    let config = read_app_config(filename).map_err(|problems| problems_report(filename, &problems))?;
+    let problems = validate_app_config(&config);
+    if problems.is_empty() {
+        Ok(config)
+    } else {
+        Err(problems_report(filename, &problems))
+    }
+}
+
+/// Checks the whole configuration, the mappings of the profiles included, instead of stopping at the first problem
+pub fn validate_app_config(config: &AppConfig) -> Vec<ConfigProblem> {
+    let mut problems = Vec::new();
+
+    validate_mappings("mapping.config", &config.mapping.config, &mut problems);
+    validate_backend_support("mapping.config", &config.mapping.config, &config.input, &mut problems);
+    for (name, profile) in config.profiles.iter().collect::<BTreeMap<_, _>>() {
+        let list = format!("profiles.{}.config", name);
+        validate_mappings(&list, &profile.config, &mut problems);
+        validate_backend_support(&list, &profile.config, &config.input, &mut problems);
+    }
+    let profile = &config.profile;
+    if !profile.default.is_empty() && !config.profiles.contains_key(&profile.default) {
+        problems.push(ConfigProblem::new("profile.default", &format!("unknown profile {}", profile.default)));
+    }
+    for (game, name) in profile.stream_categories.iter().collect::<BTreeMap<_, _>>() {
+        if !config.profiles.contains_key(name) {
+            problems.push(ConfigProblem::new(&format!("profile.stream_categories.\"{}\"", game), &format!("unknown profile {}", name)));
+        }
+    }
+
+    let input_backends = std::iter::once((s!("input.backend"), &config.input.backend))
+        .chain(config.input.categories.iter().map(|(category, backend)| (format!("input.categories.{}", category), backend)));
+    for (location, backend) in input_backends.collect::<BTreeMap<_, _>>() {
+        check(&location, input_backend_birth(backend), &mut problems);
+    }
+    check("gamepad.backend", gamepad_backend_birth(&config.gamepad.backend), &mut problems);
+
+    let pause = &config.pause;
+    if !pause.toggle_hotkey.is_empty() && key_code(&pause.toggle_hotkey).is_none() {
+        problems.push(ConfigProblem::new("pause.toggle_hotkey", &format!("unknown key {}", pause.toggle_hotkey)));
+    }
+    let pause_conditions = std::iter::once((s!("pause.conditions"), &pause.conditions))
+        .chain(pause.categories.iter().map(|(category, conditions)| (format!("pause.categories.{}", category), conditions)));
+    for (location, conditions) in pause_conditions.collect::<BTreeMap<_, _>>() {
+        for (index, condition) in conditions.iter().enumerate() {
+            check(&format!("{}[{}]", location, index), pause_condition_birth(condition), &mut problems);
+        }
+    }
+
+    check("target_window", window_guard_birth(&config.target_window.target), &mut problems);
+    for (category, target) in config.target_window.categories.iter().collect::<BTreeMap<_, _>>() {
+        check(&format!("target_window.categories.{}", category), window_guard_birth(target), &mut problems);
+    }
+
+    let locks = SharedLocks::default();
+    check("queue", queue_policy_birth(&config.queue.general), &mut problems);
+    check("queue", category_locks_birth("general", &config.queue.general, &locks), &mut problems);
+    for (category, queue) in config.queue.categories.iter().collect::<BTreeMap<_, _>>() {
+        let location = format!("queue.categories.{}", category);
+        check(&location, queue_policy_birth(queue), &mut problems);
+        check(&location, category_locks_birth(category, queue, &locks), &mut problems);
+    }
+
+    for (index, command) in config.control.commands.iter().enumerate() {
+        check(&format!("control.commands[{}] `{}`", index, command.trigger), control_command_birth(command), &mut problems);
+    }
+    check("shutdown", shutdown_policy_birth(&config.shutdown), &mut problems);
+
+    problems
+}
+
+/// Checks a list of mappings. An id already mapped before without limit is a problem, the first mapping always wins
+fn validate_mappings(list: &str, mappings: &[MappingConfig], problems: &mut Vec<ConfigProblem>) {
+    for (index, mapping) in mappings.iter().enumerate() {
+        let location = format!("{}[{}] `{}`", list, index, mapping.id);
+
+        let comparisons: &[&str] = match mapping.source.as_str() {
+            "message" => &["", "prefix"],
+            "action" => &["", "range"],
+            wrong_source => {
+                problems.push(ConfigProblem::new(&location, &format!("unknown source {}, it can be message or action", wrong_source)));
+                &[""]
+            }
+        };
+        if mapping.source == "action" && !ACTION_NAMES.contains(&mapping.name.as_str()) {
+            problems.push(ConfigProblem::new(&location, &format!("unknown action name {}, it can be reward_redeem or bits", mapping.name)));
+        }
+        if !comparisons.contains(&mapping.comparison.as_str()) {
+            problems.push(ConfigProblem::new(&location, &format!("wrong comparison {} for source {}", mapping.comparison, mapping.source)));
+        } else if mapping.comparison == "range" {
+            check(&location, comparison_range_builder(mapping.id.clone()), problems);
+        }
+
+        if mapping.actions.is_empty() {
+            problems.push(ConfigProblem::new(&location, "at least one action is required, found 0"));
+        }
+        for (action_index, action) in mapping.actions.iter().enumerate() {
+            check(&format!("{} actions[{}] `{}`", location, action_index, action), action_birth(action), problems);
+        }
+        check(&location, scheduling_birth(mapping), problems);
+
+        let shadowing = mappings[..index].iter().position(|previous| previous.limit.is_none()
+            && previous.source == mapping.source && previous.id == mapping.id && previous.name == mapping.name && previous.comparison == mapping.comparison);
+        if let Some(previous) = shadowing {
+            problems.push(ConfigProblem::new(&location, &format!("duplicate id, never triggered because {}[{}] matches first", list, previous)));
+        }
+    }
+}
+
+/// Actions the input backend of their category can't send
+fn validate_backend_support(list: &str, mappings: &[MappingConfig], input: &InputConfig, problems: &mut Vec<ConfigProblem>) {
+    for (index, mapping) in mappings.iter().enumerate() {
+        let category = if mapping.category.is_empty() { UNCATEGORIZED_CHANNEL_NAME } else { &mapping.category };
+        if input_backend_birth(input.category_backend(category)) != Ok(InputBackend::Enigo) || SUPPORTS_NORMALIZED_MOVES {
+            continue;
+        }
+        for (action_index, action) in mapping.actions.iter().enumerate().filter(|(_, action)| action.starts_with("man")) {
+            problems.push(ConfigProblem::new(
+                &format!("{}[{}] `{}` actions[{}] `{}`", list, index, mapping.id, action_index, action),
+                "normalised mouse moves need the screen size, the enigo backend can't read it on this OS"
+            ));
+        }
+    }
+}
+
+/// The configuration is built by functions returning the wrong value as error, their error is the problem
+fn check<T>(location: &str, built: Result<T, String>, problems: &mut Vec<ConfigProblem>) {
+    if let Err(e) = built {
+        problems.push(ConfigProblem::new(location, &e));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::test_config::{ConfigFile, VALID_CONFIG};
+
+    #[test]
+    fn every_wrong_mapping_then_every_problem_with_its_index() {
+        let config_file = ConfigFile::new("validation-mappings", &format!("{}{}", VALID_CONFIG, r#"
+            [[mapping.config]]
+            source = "whisper"
+            id = "!hello"
+            actions = ["kd32"]
+
+            [[mapping.config]]
+            source = "message"
+            id = "!run"
+            comparison = "range"
+            actions = ["zz32", "kp:Nope"]
+
+            [[mapping.config]]
+            source = "action"
+            name = "bits"
+            id = "10-"
+            comparison = "range"
+            actions = []
+
+            [[mapping.config]]
+            source = "message"
+            id = "!jump"
+            actions = ["kd38"]
+        "#));
+
+        let report = validated_app_config(&config_file.filename()).err().unwrap();
+
+        assert!(report.contains("7 problem(s)"), "{}", report);
+        assert!(report.contains("mapping.config[1] `!hello`: unknown source whisper"), "{}", report);
+        assert!(report.contains("mapping.config[2] `!run`: wrong comparison range for source message"), "{}", report);
+        assert!(report.contains("mapping.config[2] `!run` actions[0] `zz32`: Provided wrong action description zz32"), "{}", report);
+        assert!(report.contains("mapping.config[2] `!run` actions[1] `kp:Nope`: Provided unknown key Nope"), "{}", report);
+        assert!(report.contains("mapping.config[3] `10-`: Range comparison failed"), "{}", report);
+        assert!(report.contains("mapping.config[3] `10-`: at least one action is required"), "{}", report);
+        assert!(report.contains("mapping.config[4] `!jump`: duplicate id, never triggered because mapping.config[0] matches first"), "{}", report);
+    }
+
+    #[test]
+    fn wrong_settings_then_each_setting_reported() {
+        let config_file = ConfigFile::new("validation-settings", &format!("{}{}", VALID_CONFIG, r#"
+        [profile]
+        default = "racing"
+        [input]
+        backend = "keyboard"
+        [queue]
+        overflow = "explode"
+        [shutdown]
+        mode = "wait"
+        "#));
+
+        let problems = validate_app_config(&read_app_config(&config_file.filename()).unwrap());
+
+        assert_eq!(problems, vec![
+            ConfigProblem::new("profile.default", "unknown profile racing"),
+            ConfigProblem::new("input.backend", "Provided unknown input backend keyboard, it can be native, enigo, recording or remote"),
+            ConfigProblem::new("queue", "Provided wrong queue overflow policy explode"),
+            ConfigProblem::new("shutdown", "Provided wrong shutdown mode wait")
+        ]);
+    }
+
+    #[test]
+    fn wrong_toml_then_line_and_column() {
+        let config_file = ConfigFile::new("validation-syntax", &VALID_CONFIG.replace("actions = [\"kd32\"]", "actions = [\"kd32\""));
+
+        let problems = read_app_config(&config_file.filename()).err().unwrap();
+
+        assert_eq!(problems, vec![ConfigProblem::new("line 13, column 9", "expected a right bracket, found an identifier")]);
+    }
+
+    #[test]
+    #[cfg(not(any(windows, target_os = "macos")))]
+    fn normalized_move_on_enigo_category_then_reported() {
+        let config_file = ConfigFile::new("validation-enigo", &format!("{}{}", VALID_CONFIG, r#"
+            [[mapping.config]]
+            source = "message"
+            id = "!center"
+            category = "mouse"
+            actions = ["man0.5x0.5"]
+        [input.categories]
+        mouse = "enigo"
+        "#));
+
+        let problems = validate_app_config(&read_app_config(&config_file.filename()).unwrap());
+
+        assert_eq!(problems, vec![ConfigProblem::new(
+            "mapping.config[1] `!center` actions[0] `man0.5x0.5`",
+            "normalised mouse moves need the screen size, the enigo backend can't read it on this OS"
+        )]);
+    }
+
+    #[test]
+    fn valid_config_then_no_problem() {
+        let config_file = ConfigFile::new("validation-valid", VALID_CONFIG);
+
+        assert!(validated_app_config(&config_file.filename()).is_ok());
+    }
+}
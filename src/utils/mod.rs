@@ -1,3 +1,7 @@
 pub mod run_on_stream;
 pub mod app_config;
 pub mod string;
+pub mod config_validation;
+pub mod cli;
+#[cfg(test)]
+pub mod test_config;
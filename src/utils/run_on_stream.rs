@@ -45,22 +45,30 @@ pub async fn run_on_stream<T: Display, O>(items: impl Stream<Item = StreamEvent<
     }
 }
 
-/**
- * `stop_on_event!(my_stream, { MyEvents:MyEvent(ref evt) => evt.is_stopping, _ => false })`
- *
- * First parameter is the stream, second parameter is a match body (without `match ev`)
- */
-#[macro_export]
-macro_rules! stop_on_event {
-    ($stream: ident, $match_body:tt) => {{
-        use crate::utils::run_on_stream::StreamEvent;
+/// Control layer between a stream and its receiver, for each item the control decides if it goes on,
+/// stops the stream or is consumed (None)
+pub fn control_on_stream<T>(items: impl Stream<Item = T>, control: impl FnMut(T) -> Option<StreamEvent<T>>) -> impl Stream<Item = StreamEvent<T>> {
+    tokio_stream::StreamExt::filter_map(items, control)
+}
 
-        $stream.map(|ev| {
-            if match ev $match_body {
-                StreamEvent::Stop
-            } else {
-                StreamEvent::Item(ev)
-            }
-        })
-    }};
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::stream;
+
+    #[tokio::test]
+    async fn control_passes_consumes_and_stops_items() {
+        let controlled = control_on_stream(stream::iter(vec![1, 2, 3, 4]), |item| match item {
+            2 => None,
+            3 => Some(StreamEvent::Stop),
+            _ => Some(StreamEvent::Item(item))
+        });
+
+        let events: Vec<Option<i32>> = controlled.map(|event| match event {
+            StreamEvent::Item(item) => Some(item),
+            StreamEvent::Stop => None
+        }).collect().await;
+
+        assert_eq!(events, vec![Some(1), None, Some(4)]);
+    }
 }
@@ -0,0 +1,47 @@
+use std::env::temp_dir;
+use std::fs;
+use std::path::PathBuf;
+use std::process::id;
+
+/// Smallest valid configuration of the tests, one mapping in the movement category
+pub const VALID_CONFIG: &str = r#"
+    version = "1.0"
+    [twitch_stream]
+    user = "bot"
+    token = "token"
+    client_id = "client"
+    channel = "channel"
+    [mapping]
+        [[mapping.config]]
+        source = "message"
+        id = "!jump"
+        actions = ["kd32"]
+        category = "movement"
+"#;
+
+/// Configuration file written in the temporary directory, removed when dropped. The name has to be unique among the tests
+pub struct ConfigFile {
+    path: PathBuf
+}
+
+impl ConfigFile {
+    pub fn new(name: &str, content: &str) -> Self {
+        let config_file = ConfigFile { path: temp_dir().join(format!("twitch-commands-{}-{}.toml", name, id())) };
+        config_file.write(content);
+        config_file
+    }
+
+    pub fn write(&self, content: &str) {
+        fs::write(&self.path, content).unwrap();
+    }
+
+    pub fn filename(&self) -> String {
+        self.path.to_string_lossy().to_string()
+    }
+}
+
+impl Drop for ConfigFile {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}